@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+
+use crate::exporter;
+
+/// Durée de chaque fichier source (s), indexée par chemin
+pub(crate) type SourceDurations = HashMap<String, f64>;
+
+/// Sonde la durée des clips de toutes les couches. Le jeton de l'export garde les durées,
+/// chaque fichier n'est sondé qu'une fois par export même s'il est découpé en chunks.
+pub(crate) fn source_durations(layers: &[AudioLayer], export_id: &str) -> SourceDurations {
+    let token = exporter::export_token(export_id);
+    let mut durations = SourceDurations::new();
+    for clip in layers.iter().flat_map(|l| &l.clips) {
+        let path = match clip {
+            AudioSource::Path(path) => path,
+            AudioSource::Clip(clip) => &clip.path,
+        };
+        if !durations.contains_key(path) {
+            durations.insert(path.clone(), token.source_duration_s(path));
+        }
+    }
+    durations
+}
+
+/// Clip audio placé sur la timeline de l'éditeur
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AudioClip {
+    pub path: String,
+    /// Début du clip dans la vidéo exportée, en ms (fin du clip précédent par défaut)
+    #[serde(default)]
+    pub timeline_start_ms: Option<i32>,
+    /// Fin du clip dans la vidéo exportée, en ms (fin de la plage lue par défaut)
+    #[serde(default)]
+    pub timeline_end_ms: Option<i32>,
+    /// Point d'entrée dans la source, en ms
+    #[serde(default)]
+    pub source_in_ms: Option<i32>,
+    /// Point de sortie dans la source, en ms (fin du fichier par défaut)
+    #[serde(default)]
+    pub source_out_ms: Option<i32>,
+}
+
+/// Audio reçu du frontend : un simple chemin (clips mis bout à bout), ou un clip placé
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum AudioSource {
+    Path(String),
+    Clip(AudioClip),
+}
+
+impl From<AudioSource> for AudioClip {
+    fn from(source: AudioSource) -> Self {
+        match source {
+            AudioSource::Path(path) => AudioClip {
+                path,
+                timeline_start_ms: None,
+                timeline_end_ms: None,
+                source_in_ms: None,
+                source_out_ms: None,
+            },
+            AudioSource::Clip(clip) => clip,
+        }
+    }
+}
+
+/// Baisse automatique d'une couche pendant que la récitation joue (`sidechaincompress`)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Ducking {
+    /// Niveau de la récitation (0..1) à partir duquel la couche baisse
+    #[serde(default = "default_duck_threshold")]
+    pub threshold: f64,
+    /// Force de la baisse (1 = aucune, 20 = maximale)
+    #[serde(default = "default_duck_ratio")]
+    pub ratio: f64,
+    #[serde(default = "default_duck_attack_ms")]
+    pub attack_ms: f64,
+    /// Temps de remontée après la fin d'un verset
+    #[serde(default = "default_duck_release_ms")]
+    pub release_ms: f64,
+}
+
+fn default_duck_threshold() -> f64 {
+    0.05
+}
+
+fn default_duck_ratio() -> f64 {
+    8.0
+}
+
+fn default_duck_attack_ms() -> f64 {
+    20.0
+}
+
+fn default_duck_release_ms() -> f64 {
+    400.0
+}
+
+fn default_volume() -> f64 {
+    1.0
+}
+
+/// Couche audio (récitation, ambiance, nasheed), mixée avec les autres
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AudioLayer {
+    pub clips: Vec<AudioSource>,
+    /// Gain linéaire (1.0 = inchangé)
+    #[serde(default = "default_volume")]
+    pub volume: f64,
+    /// Fondu d'entrée au début du premier clip, en ms
+    #[serde(default)]
+    pub fade_in_ms: Option<i32>,
+    /// Fondu de sortie à la fin de la couche, en ms
+    #[serde(default)]
+    pub fade_out_ms: Option<i32>,
+    /// Rejoue la couche jusqu'à `end_ms`, ou jusqu'à la fin de la plage exportée
+    #[serde(default)]
+    pub loop_until_end: bool,
+    /// Fin de la couche dans la vidéo exportée, en ms. Pour un export en chunks, une couche en
+    /// boucle sans fin reçoit celle de l'export (`pin_loop_layer_ends`).
+    #[serde(default)]
+    pub end_ms: Option<i32>,
+    /// Si présent, la couche baisse quand les couches sans ducking (la récitation) jouent
+    #[serde(default)]
+    pub ducking: Option<Ducking>,
+}
+
+impl AudioLayer {
+    /// Couche de récitation, construite à partir du paramètre `audios`
+    pub(crate) fn recitation(clips: Vec<AudioSource>) -> Self {
+        AudioLayer {
+            clips,
+            volume: 1.0,
+            fade_in_ms: None,
+            fade_out_ms: None,
+            loop_until_end: false,
+            end_ms: None,
+            ducking: None,
+        }
+    }
+
+    /// Place les clips de la couche et garde ceux qui recoupent `[start_s, start_s + duration_s]`
+    pub(crate) fn place(&self, start_s: f64, duration_s: f64, durations: &SourceDurations) -> LayerPlacement {
+        let range_end_s = start_s + duration_s;
+        let clips: Vec<AudioClip> = self.clips.iter().cloned().map(AudioClip::from).collect();
+        let mut spans = timeline_spans(&clips, durations);
+        let layer_start_s = spans.iter().map(|s| s.start_s).fold(f64::INFINITY, f64::min);
+        let content_end_s = spans.iter().map(|s| s.start_s + s.len_s).fold(0.0, f64::max);
+        let mut layer_end_s = self.end_ms.map(|e| e as f64 / 1000.0).unwrap_or(content_end_s);
+
+        // Boucle : le contenu de la couche est répété, seules les répétitions utiles sont générées
+        if self.loop_until_end && !spans.is_empty() {
+            if self.end_ms.is_none() {
+                layer_end_s = range_end_s;
+            }
+            let period_s = content_end_s - layer_start_s;
+            if period_s > 0.01 {
+                let until_s = layer_end_s.min(range_end_s);
+                let first_k = ((start_s - content_end_s) / period_s).floor().max(1.0) as i64;
+                let original = spans.clone();
+                let mut k = first_k;
+                while layer_start_s + k as f64 * period_s < until_s {
+                    spans.extend(original.iter().map(|s| ClipSpan { start_s: s.start_s + k as f64 * period_s, ..s.clone() }));
+                    k += 1;
+                }
+            }
+        }
+
+        // Rien n'est lu après la fin de la couche
+        for span in spans.iter_mut() {
+            span.len_s = span.len_s.min(layer_end_s - span.start_s);
+        }
+
+        let mut clips = Vec::new();
+        for span in spans {
+            let clip_end_s = span.start_s + span.len_s;
+            if span.len_s <= 1e-6 || clip_end_s <= start_s + 1e-6 || span.start_s >= range_end_s - 1e-6 {
+                continue;
+            }
+            // Début du clip avant l'export : on saute la partie déjà passée
+            let skip_s = (start_s - span.start_s).max(0.0);
+            clips.push(PlacedAudio {
+                path: span.path,
+                delay_s: (span.start_s - start_s).max(0.0),
+                source_in_s: span.source_in_s + skip_s,
+                source_out_s: span.source_in_s + span.len_s.min(range_end_s - span.start_s),
+            });
+        }
+
+        LayerPlacement { clips, start_s: layer_start_s, end_s: layer_end_s }
+    }
+
+    /// Volume et fondus, calculés sur le temps de la vidéo complète pour que les chunks se raccordent
+    fn gain_filter(&self, placement: &LayerPlacement, start_s: f64) -> Option<String> {
+        let mut factors = Vec::new();
+        if (self.volume - 1.0).abs() > 1e-6 {
+            factors.push(format!("{:.4}", self.volume.max(0.0)));
+        }
+        if let Some(fade_ms) = self.fade_in_ms.filter(|&f| f > 0) {
+            factors.push(format!(
+                "clip((t+{:.6}-{:.6})/{:.6},0,1)",
+                start_s, placement.start_s, fade_ms as f64 / 1000.0
+            ));
+        }
+        if let Some(fade_ms) = self.fade_out_ms.filter(|&f| f > 0) {
+            factors.push(format!(
+                "clip(({:.6}-t-{:.6})/{:.6},0,1)",
+                placement.end_s, start_s, fade_ms as f64 / 1000.0
+            ));
+        }
+        if factors.is_empty() {
+            None
+        } else {
+            Some(format!("volume='{}':eval=frame", factors.join("*")))
+        }
+    }
+}
+
+/// Position absolue d'un clip sur la timeline (s)
+#[derive(Debug, Clone)]
+struct ClipSpan {
+    path: String,
+    start_s: f64,
+    source_in_s: f64,
+    len_s: f64,
+}
+
+/// Place les clips : un clip sans position commence à la fin du précédent.
+/// Une source absente de `durations` est considérée comme vide.
+fn timeline_spans(clips: &[AudioClip], durations: &SourceDurations) -> Vec<ClipSpan> {
+    let mut spans = Vec::new();
+    let mut cursor_s = 0.0;
+    for clip in clips {
+        let source_s = durations.get(&clip.path).copied().unwrap_or(0.0);
+        let source_in = (clip.source_in_ms.unwrap_or(0).max(0) as f64 / 1000.0).min(source_s);
+        let source_out = clip.source_out_ms.map(|o| o as f64 / 1000.0).unwrap_or(source_s).clamp(source_in, source_s);
+        let start_s = clip.timeline_start_ms.map(|s| s.max(0) as f64 / 1000.0).unwrap_or(cursor_s);
+        let mut len_s = source_out - source_in;
+        if let Some(timeline_end) = clip.timeline_end_ms {
+            len_s = len_s.min(timeline_end as f64 / 1000.0 - start_s);
+        }
+        if len_s <= 1e-6 {
+            continue;
+        }
+        cursor_s = start_s + len_s;
+        spans.push(ClipSpan { path: clip.path.clone(), start_s, source_in_s: source_in, len_s });
+    }
+    spans
+}
+
+/// Portion d'un clip audio à lire dans un export, positions relatives au début de l'export
+#[derive(Debug, Clone)]
+pub(crate) struct PlacedAudio {
+    pub path: String,
+    /// Silence avant le clip (s)
+    pub delay_s: f64,
+    /// Plage lue dans la source (s)
+    pub source_in_s: f64,
+    pub source_out_s: f64,
+}
+
+/// Clips d'une couche à lire dans un export, avec les bornes de la couche sur la timeline (s)
+#[derive(Debug, Clone)]
+pub(crate) struct LayerPlacement {
+    pub clips: Vec<PlacedAudio>,
+    pub start_s: f64,
+    pub end_s: f64,
+}
+
+/// Mixe une liste de flux vers `out` (sans changement de niveau)
+fn mix(lines: &mut Vec<String>, labels: &[String], out: &str) {
+    if labels.len() == 1 {
+        lines.push(format!("[{}]anull[{}]", labels[0], out));
+    } else {
+        let ins: String = labels.iter().map(|l| format!("[{}]", l)).collect();
+        lines.push(format!("{}amix=inputs={}:duration=longest:normalize=0[{}]", ins, labels.len(), out));
+    }
+}
+
+/// Filtres mixant les couches vers `[aout]`, suivis de `post_filter` s'il est fourni.
+///
+/// Les entrées ffmpeg sont les clips de `placements`, dans l'ordre, à partir de `first_input_idx`.
+pub(crate) fn mix_filter(
+    layers: &[AudioLayer],
+    placements: &[LayerPlacement],
+    first_input_idx: usize,
+    start_s: f64,
+    duration_s: f64,
+    post_filter: Option<&str>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut input_idx = first_input_idx;
+    let layered = placements.iter().filter(|p| !p.clips.is_empty()).count() > 1;
+    let mut main_labels = Vec::new();
+    let mut ducked = Vec::new();
+
+    for (l, (layer, placement)) in layers.iter().zip(placements).enumerate() {
+        if placement.clips.is_empty() {
+            continue;
+        }
+
+        // Clips de la couche, silence entre eux
+        let mut clip_labels = Vec::new();
+        for (j, clip) in placement.clips.iter().enumerate() {
+            let delay = if clip.delay_s > 1e-6 {
+                format!(",adelay=delays={}:all=1", (clip.delay_s * 1000.0).round() as i64)
+            } else {
+                String::new()
+            };
+            lines.push(format!(
+                "[{}:a]aresample=48000,atrim=start={:.6}:end={:.6},asetpts=PTS-STARTPTS{}[aa{}_{}]",
+                input_idx, clip.source_in_s, clip.source_out_s, delay, l, j
+            ));
+            clip_labels.push(format!("aa{}_{}", l, j));
+            input_idx += 1;
+        }
+        let mut label = format!("layer{}", l);
+        mix(&mut lines, &clip_labels, &label);
+
+        // Les couches mixées doivent partager le même format
+        let mut chain = Vec::new();
+        if layered {
+            chain.push("aformat=sample_fmts=fltp:sample_rates=48000:channel_layouts=stereo".to_string());
+        }
+        chain.extend(layer.gain_filter(placement, start_s));
+        if !chain.is_empty() {
+            lines.push(format!("[{}]{}[{}g]", label, chain.join(","), label));
+            label = format!("{}g", label);
+        }
+
+        match &layer.ducking {
+            Some(ducking) => ducked.push((label, ducking)),
+            None => main_labels.push(label),
+        }
+    }
+
+    // Sans couche de récitation, le ducking n'a rien pour se déclencher
+    if main_labels.is_empty() {
+        if !ducked.is_empty() {
+            println!("[audio] Ducking ignoré: aucune couche de récitation");
+        }
+        main_labels = ducked.drain(..).map(|(label, _)| label).collect();
+    }
+
+    let mut final_labels = Vec::new();
+    if ducked.is_empty() {
+        final_labels = main_labels;
+    } else {
+        // La récitation sert de signal de déclenchement à chaque couche baissée
+        mix(&mut lines, &main_labels, "amain");
+        let sc: String = (0..ducked.len()).map(|k| format!("[sc{}]", k)).collect();
+        lines.push(format!("[amain]asplit={}[amainout]{}", ducked.len() + 1, sc));
+        final_labels.push("amainout".to_string());
+        for (k, (label, ducking)) in ducked.iter().enumerate() {
+            // apad : la couche continue de jouer (sans baisse) après la fin de la récitation
+            lines.push(format!("[sc{}]apad[scp{}]", k, k));
+            lines.push(format!(
+                "[{}][scp{}]sidechaincompress=threshold={:.6}:ratio={:.3}:attack={:.3}:release={:.3}[duck{}]",
+                label, k,
+                ducking.threshold.clamp(0.000976563, 1.0),
+                ducking.ratio.clamp(1.0, 20.0),
+                ducking.attack_ms.clamp(0.01, 2000.0),
+                ducking.release_ms.clamp(0.01, 9000.0),
+                k
+            ));
+            final_labels.push(format!("duck{}", k));
+        }
+    }
+
+    mix(&mut lines, &final_labels, "amixed");
+    let post = post_filter.map(|f| format!(",{}", f)).unwrap_or_default();
+    lines.push(format!("[amixed]atrim=end={:.6}{}[aout]", duration_s, post));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(path: &str, start_ms: Option<i32>, source_in_ms: Option<i32>, source_out_ms: Option<i32>) -> AudioSource {
+        AudioSource::Clip(AudioClip {
+            path: path.to_string(),
+            timeline_start_ms: start_ms,
+            timeline_end_ms: None,
+            source_in_ms,
+            source_out_ms,
+        })
+    }
+
+    fn durations(entries: &[(&str, f64)]) -> SourceDurations {
+        entries.iter().map(|(p, d)| (p.to_string(), *d)).collect()
+    }
+
+    fn ranges(placement: &LayerPlacement) -> Vec<(String, f64, f64, f64)> {
+        placement
+            .clips
+            .iter()
+            .map(|c| (c.path.clone(), c.delay_s, c.source_in_s, c.source_out_s))
+            .collect()
+    }
+
+    fn assert_ranges(actual: Vec<(String, f64, f64, f64)>, expected: &[(&str, f64, f64, f64)]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            assert_eq!(a.0, e.0);
+            assert!((a.1 - e.1).abs() < 1e-6, "delay {:?} != {:?}", a, e);
+            assert!((a.2 - e.2).abs() < 1e-6, "in {:?} != {:?}", a, e);
+            assert!((a.3 - e.3).abs() < 1e-6, "out {:?} != {:?}", a, e);
+        }
+    }
+
+    #[test]
+    fn unpositioned_clips_are_placed_back_to_back() {
+        let layer = AudioLayer::recitation(vec![
+            AudioSource::Path("a.mp3".to_string()),
+            AudioSource::Path("b.mp3".to_string()),
+        ]);
+        let durations = durations(&[("a.mp3", 10.0), ("b.mp3", 5.0)]);
+
+        let placement = layer.place(0.0, 20.0, &durations);
+        assert_ranges(ranges(&placement), &[("a.mp3", 0.0, 0.0, 10.0), ("b.mp3", 10.0, 0.0, 5.0)]);
+        assert_eq!(placement.start_s, 0.0);
+        assert_eq!(placement.end_s, 15.0);
+    }
+
+    #[test]
+    fn chunk_range_skips_the_elapsed_part_of_a_clip() {
+        let layer = AudioLayer::recitation(vec![
+            AudioSource::Path("a.mp3".to_string()),
+            AudioSource::Path("b.mp3".to_string()),
+        ]);
+        let durations = durations(&[("a.mp3", 10.0), ("b.mp3", 5.0)]);
+
+        let placement = layer.place(8.0, 5.0, &durations);
+        assert_ranges(ranges(&placement), &[("a.mp3", 0.0, 8.0, 10.0), ("b.mp3", 2.0, 0.0, 3.0)]);
+    }
+
+    #[test]
+    fn positioned_clip_reads_its_source_range() {
+        let layer = AudioLayer::recitation(vec![clip("a.mp3", Some(3000), Some(1000), Some(4000))]);
+        let durations = durations(&[("a.mp3", 10.0)]);
+
+        let placement = layer.place(0.0, 10.0, &durations);
+        assert_ranges(ranges(&placement), &[("a.mp3", 3.0, 1.0, 4.0)]);
+        assert_eq!(placement.start_s, 3.0);
+        assert_eq!(placement.end_s, 6.0);
+    }
+
+    #[test]
+    fn timeline_end_and_source_duration_bound_a_clip() {
+        let clips = vec![
+            AudioClip {
+                path: "a.mp3".to_string(),
+                timeline_start_ms: Some(1000),
+                timeline_end_ms: Some(3000),
+                source_in_ms: None,
+                source_out_ms: None,
+            },
+            AudioClip::from(clip("b.mp3", None, Some(500), Some(60_000))),
+        ];
+        let spans = timeline_spans(&clips, &durations(&[("a.mp3", 10.0), ("b.mp3", 4.0)]));
+
+        assert_eq!(spans.len(), 2);
+        assert_eq!((spans[0].start_s, spans[0].len_s), (1.0, 2.0));
+        assert_eq!((spans[1].start_s, spans[1].source_in_s, spans[1].len_s), (3.0, 0.5, 3.5));
+    }
+
+    #[test]
+    fn unknown_or_empty_sources_are_skipped() {
+        let layer = AudioLayer::recitation(vec![
+            AudioSource::Path("missing.mp3".to_string()),
+            AudioSource::Path("a.mp3".to_string()),
+        ]);
+        let placement = layer.place(0.0, 10.0, &durations(&[("a.mp3", 2.0)]));
+        assert_ranges(ranges(&placement), &[("a.mp3", 0.0, 0.0, 2.0)]);
+    }
+
+    #[test]
+    fn looped_layer_only_generates_repetitions_in_range() {
+        let mut layer = AudioLayer::recitation(vec![AudioSource::Path("loop.mp3".to_string())]);
+        layer.loop_until_end = true;
+        let durations = durations(&[("loop.mp3", 4.0)]);
+
+        // Répétitions à 8 s et 12 s ; la dernière est coupée à la fin de la plage
+        let placement = layer.place(10.0, 5.0, &durations);
+        assert_ranges(ranges(&placement), &[("loop.mp3", 0.0, 2.0, 4.0), ("loop.mp3", 2.0, 0.0, 3.0)]);
+        assert_eq!(placement.end_s, 15.0);
+    }
+
+    #[test]
+    fn looped_layer_stops_at_end_ms() {
+        let mut layer = AudioLayer::recitation(vec![AudioSource::Path("loop.mp3".to_string())]);
+        layer.loop_until_end = true;
+        layer.end_ms = Some(10_000);
+        let durations = durations(&[("loop.mp3", 4.0)]);
+
+        let placement = layer.place(0.0, 20.0, &durations);
+        assert_ranges(
+            ranges(&placement),
+            &[("loop.mp3", 0.0, 0.0, 4.0), ("loop.mp3", 4.0, 0.0, 4.0), ("loop.mp3", 8.0, 0.0, 2.0)],
+        );
+        assert_eq!(placement.end_s, 10.0);
+    }
+
+    #[test]
+    fn single_clip_is_trimmed_and_cut_to_the_range() {
+        let layer = AudioLayer::recitation(vec![AudioSource::Path("a.mp3".to_string())]);
+        let placements = vec![layer.place(2.0, 5.0, &durations(&[("a.mp3", 10.0)]))];
+
+        let lines = mix_filter(&[layer], &placements, 1, 2.0, 5.0, None);
+        assert_eq!(
+            lines,
+            vec![
+                "[1:a]aresample=48000,atrim=start=2.000000:end=7.000000,asetpts=PTS-STARTPTS[aa0_0]".to_string(),
+                "[aa0_0]anull[layer0]".to_string(),
+                "[layer0]anull[amixed]".to_string(),
+                "[amixed]atrim=end=5.000000[aout]".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn ducked_layer_is_compressed_by_the_recitation() {
+        let recitation = AudioLayer::recitation(vec![AudioSource::Path("a.mp3".to_string())]);
+        let mut nasheed = AudioLayer::recitation(vec![AudioSource::Path("n.mp3".to_string())]);
+        nasheed.volume = 0.5;
+        nasheed.ducking = Some(Ducking { threshold: 0.05, ratio: 8.0, attack_ms: 20.0, release_ms: 400.0 });
+        let durations = durations(&[("a.mp3", 10.0), ("n.mp3", 10.0)]);
+        let layers = vec![recitation, nasheed];
+        let placements: Vec<LayerPlacement> = layers.iter().map(|l| l.place(0.0, 10.0, &durations)).collect();
+
+        let lines = mix_filter(&layers, &placements, 0, 0.0, 10.0, Some("loudnorm"));
+        assert!(lines[0].starts_with("[0:a]"));
+        assert!(lines.iter().any(|l| l.starts_with("[1:a]")));
+        assert!(lines.iter().any(|l| l.contains("volume='0.5000':eval=frame")));
+        assert!(lines.iter().any(|l| l == "[amain]asplit=2[amainout][sc0]"));
+        assert!(lines.iter().any(|l| l.starts_with("[layer1g][scp0]sidechaincompress=threshold=0.050000:ratio=8.000")));
+        assert!(lines.iter().any(|l| l.starts_with("[amainout][duck0]amix=inputs=2")));
+        assert_eq!(lines.last().unwrap(), "[amixed]atrim=end=10.000000,loudnorm[aout]");
+    }
+
+    #[test]
+    fn ducking_without_recitation_is_ignored() {
+        let mut nasheed = AudioLayer::recitation(vec![AudioSource::Path("n.mp3".to_string())]);
+        nasheed.ducking = Some(Ducking { threshold: 0.05, ratio: 8.0, attack_ms: 20.0, release_ms: 400.0 });
+        let placements = vec![nasheed.place(0.0, 10.0, &durations(&[("n.mp3", 10.0)]))];
+
+        let lines = mix_filter(&[nasheed], &placements, 0, 0.0, 10.0, None);
+        assert!(!lines.iter().any(|l| l.contains("sidechaincompress")));
+        assert_eq!(lines.last().unwrap(), "[amixed]atrim=end=10.000000[aout]");
+    }
+
+    #[test]
+    fn looped_layer_fades_out_at_the_export_end_in_a_middle_chunk() {
+        let mut layer = AudioLayer::recitation(vec![AudioSource::Path("loop.mp3".to_string())]);
+        layer.loop_until_end = true;
+        layer.fade_out_ms = Some(2000);
+        layer.end_ms = Some(30_000);
+        let durations = durations(&[("loop.mp3", 4.0)]);
+
+        // Chunk 10 s -> 20 s d'un export de 30 s : la couche joue jusqu'au bout du chunk
+        let placement = layer.place(10.0, 10.0, &durations);
+        assert_eq!(placement.end_s, 30.0);
+        assert_eq!(placement.clips.last().map(|c| c.delay_s + c.source_out_s - c.source_in_s), Some(10.0));
+        assert_eq!(
+            layer.gain_filter(&placement, 10.0).as_deref(),
+            Some("volume='clip((30.000000-t-10.000000)/2.000000,0,1)':eval=frame")
+        );
+    }
+}
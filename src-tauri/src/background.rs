@@ -0,0 +1,289 @@
+/// Façon d'adapter une vidéo de fond au format de l'export
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FitMode {
+    /// Vidéo entière, bandes noires autour
+    #[default]
+    Contain,
+    /// Remplit le cadre, les bords qui dépassent sont recadrés
+    Cover,
+    /// Déformée pour remplir exactement le cadre
+    Stretch,
+    /// Vidéo entière, posée sur une copie agrandie, floutée et assombrie d'elle-même
+    BlurredFill,
+}
+
+// Réglages du fond flouté : réduction avant le flou (plus rapide), rayon du flou, assombrissement
+const BLUR_DOWNSCALE: i32 = 4;
+const BLUR_RADIUS: i32 = 12;
+const BLUR_BRIGHTNESS: f64 = -0.15;
+
+impl FitMode {
+    /// Filtres ffmpeg amenant l'image à `w`x`h`.
+    ///
+    /// Le fond flouté utilise des liens nommés internes : le résultat reste un graphe
+    /// à une entrée et une sortie, utilisable avec `-vf`.
+    pub(crate) fn filter(self, w: i32, h: i32) -> String {
+        match self {
+            FitMode::Contain => format!(
+                "scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black",
+                w, h, w, h
+            ),
+            FitMode::Cover => format!(
+                "scale={}:{}:force_original_aspect_ratio=increase,crop={}:{}:(in_w-{})/2:(in_h-{})/2",
+                w, h, w, h, w, h
+            ),
+            FitMode::Stretch => format!("scale={}:{}", w, h),
+            FitMode::BlurredFill => {
+                // Dimensions paires pour les encodeurs yuv420p
+                let (bw, bh) = ((w / BLUR_DOWNSCALE / 2 * 2).max(2), (h / BLUR_DOWNSCALE / 2 * 2).max(2));
+                format!(
+                    "split=2[fill][fg];\
+                     [fill]{},boxblur={}:1,eq=brightness={},scale={}:{}[blurred];\
+                     [fg]scale=w={}:h={}:force_original_aspect_ratio=decrease[contained];\
+                     [blurred][contained]overlay=(W-w)/2:(H-h)/2",
+                    FitMode::Cover.filter(bw, bh), BLUR_RADIUS, BLUR_BRIGHTNESS, w, h,
+                    w, h
+                )
+            }
+        }
+    }
+}
+
+/// Mouvement appliqué à une image de fond fixe (effet Ken Burns)
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MotionKind {
+    ZoomIn,
+    ZoomOut,
+    PanLeft,
+    PanRight,
+    /// Un des mouvements ci-dessus, tiré au hasard pour chaque export
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    #[default]
+    EaseInOut,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ImageMotion {
+    pub kind: MotionKind,
+    /// Multiplie la vitesse du mouvement (1.0 = un aller en 20 s)
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+// Durée d'un aller du mouvement à vitesse 1, puis retour en sens inverse
+const MOTION_CYCLE_S: f64 = 20.0;
+// Zoom maximal (1.2 = 20 % plus grand)
+const MOTION_ZOOM: f64 = 0.2;
+// Suréchantillonnage avant zoompan, qui travaille au pixel près et tremble sinon
+const MOTION_OVERSAMPLE: i32 = 2;
+
+impl ImageMotion {
+    /// Mouvement réel : `Random` est tiré d'après `seed` (l'export_id), identique pour tous les chunks
+    pub(crate) fn resolve_kind(&self, seed: &str) -> MotionKind {
+        const KINDS: [MotionKind; 4] = [MotionKind::ZoomIn, MotionKind::ZoomOut, MotionKind::PanLeft, MotionKind::PanRight];
+        match self.kind {
+            MotionKind::Random => KINDS[md5::compute(seed.as_bytes())[0] as usize % KINDS.len()],
+            kind => kind,
+        }
+    }
+
+    /// Filtres `fit` + `zoompan` produisant `w`x`h`.
+    ///
+    /// Le mouvement suit le temps d'affichage de l'image (`offset_s` = position au début du chunk), en
+    /// aller-retour : les chunks s'enchaînent sans saut quelle que soit la durée totale.
+    pub(crate) fn filter(&self, fit: FitMode, kind: MotionKind, w: i32, h: i32, fps: i32, offset_s: f64) -> String {
+        let speed = if self.speed.is_finite() && self.speed > 0.0 { self.speed } else { 1.0 };
+        let period_s = MOTION_CYCLE_S / speed;
+
+        // Avancement 0 -> 1 -> 0 (onde triangulaire), puis courbe d'accélération
+        let p = format!("(1-abs(1-mod((on/{}+{:.6})/{:.6},2)))", fps, offset_s, period_s);
+        let e = match self.easing {
+            Easing::Linear => p,
+            Easing::EaseInOut => format!("({p}*{p}*(3-2*{p}))", p = p),
+        };
+
+        let (z, x, y) = match kind {
+            MotionKind::ZoomOut => (
+                format!("1+{}*(1-{})", MOTION_ZOOM, e),
+                "iw/2-iw/zoom/2".to_string(),
+                "ih/2-ih/zoom/2".to_string(),
+            ),
+            MotionKind::PanLeft => (
+                format!("{}", 1.0 + MOTION_ZOOM),
+                format!("(iw-iw/zoom)*(1-{})", e),
+                "ih/2-ih/zoom/2".to_string(),
+            ),
+            MotionKind::PanRight => (
+                format!("{}", 1.0 + MOTION_ZOOM),
+                format!("(iw-iw/zoom)*{}", e),
+                "ih/2-ih/zoom/2".to_string(),
+            ),
+            _ => (
+                format!("1+{}*{}", MOTION_ZOOM, e),
+                "iw/2-iw/zoom/2".to_string(),
+                "ih/2-ih/zoom/2".to_string(),
+            ),
+        };
+
+        format!(
+            "{},zoompan=z='{}':x='{}':y='{}':d=1:s={}x{}:fps={}",
+            fit.filter(w * MOTION_OVERSAMPLE, h * MOTION_OVERSAMPLE),
+            z, x, y, w, h, fps
+        )
+    }
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+/// Vidéo (ou image) de fond avec ses options de lecture
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BackgroundClip {
+    pub path: String,
+    /// Contain par défaut pour une vidéo, Cover pour une image
+    #[serde(default)]
+    pub fit: Option<FitMode>,
+    /// Point d'entrée dans la source, en ms
+    #[serde(default, alias = "source_in_ms")]
+    pub trim_in_ms: Option<i32>,
+    /// Point de sortie dans la source, en ms (fin de la vidéo par défaut)
+    #[serde(default, alias = "source_out_ms")]
+    pub trim_out_ms: Option<i32>,
+    /// Vitesse de lecture (1.0 = normale)
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    /// Rejoue le clip jusqu'à la fin de l'export ; les clips suivants ne sont pas utilisés
+    #[serde(default)]
+    pub loop_until_end: bool,
+    /// Mouvement de caméra, pour les images uniquement
+    #[serde(default)]
+    pub motion: Option<ImageMotion>,
+    /// Durée d'affichage d'une image, en ms (jusqu'à la fin de l'export par défaut)
+    #[serde(default)]
+    pub duration_ms: Option<i32>,
+    /// Fondu enchaîné depuis le clip précédent, en ms
+    #[serde(default)]
+    pub crossfade_ms: Option<i32>,
+    /// Début du clip dans la vidéo exportée, en ms (fin du clip précédent par défaut).
+    /// Un intervalle vide avant le clip est rempli de noir.
+    #[serde(default)]
+    pub timeline_start_ms: Option<i32>,
+    /// Fin du clip dans la vidéo exportée, en ms (ignorée pour un clip en boucle)
+    #[serde(default)]
+    pub timeline_end_ms: Option<i32>,
+}
+
+impl BackgroundClip {
+    pub(crate) fn speed(&self) -> f64 {
+        if self.speed.is_finite() && self.speed > 0.0 { self.speed } else { 1.0 }
+    }
+
+    /// Plage lue dans la source (ms), bornée à sa durée
+    pub(crate) fn trim_range_ms(&self, source_ms: i64) -> (i64, i64) {
+        let trim_in = (self.trim_in_ms.unwrap_or(0) as i64).clamp(0, source_ms);
+        let trim_out = self.trim_out_ms.map(|o| o as i64).unwrap_or(source_ms).clamp(trim_in, source_ms);
+        (trim_in, trim_out)
+    }
+
+    /// Durée d'un passage du clip dans la vidéo exportée (ms), vitesse comprise
+    pub(crate) fn timeline_len_ms(&self, source_ms: i64) -> i64 {
+        let (trim_in, trim_out) = self.trim_range_ms(source_ms);
+        ((trim_out - trim_in) as f64 / self.speed()).round() as i64
+    }
+
+    /// Paramètres qui changent le rendu du clip, pour la clé du cache de prétraitement
+    pub(crate) fn cache_params(&self) -> String {
+        format!(
+            "{:?}-in{:?}-out{:?}-x{}",
+            self.fit, self.trim_in_ms, self.trim_out_ms, self.speed()
+        )
+    }
+}
+
+/// Fond reçu du frontend : un simple chemin, ou un clip avec ses options
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum BackgroundSource {
+    Path(String),
+    Clip(BackgroundClip),
+}
+
+impl From<BackgroundSource> for BackgroundClip {
+    fn from(source: BackgroundSource) -> Self {
+        match source {
+            BackgroundSource::Path(path) => BackgroundClip {
+                path,
+                fit: None,
+                trim_in_ms: None,
+                trim_out_ms: None,
+                speed: 1.0,
+                loop_until_end: false,
+                motion: None,
+                duration_ms: None,
+                crossfade_ms: None,
+                timeline_start_ms: None,
+                timeline_end_ms: None,
+            },
+            BackgroundSource::Clip(clip) => clip,
+        }
+    }
+}
+
+/// Vidéo de fond prête pour le filter_complex final
+#[derive(Debug, Clone)]
+pub(crate) struct PreparedBackground {
+    pub path: String,
+    /// Si présent, la vidéo est rejouée en boucle en commençant à cette position (s)
+    pub loop_from_s: Option<f64>,
+    /// Durée du fondu depuis le fond précédent (s), 0 pour une coupe franche
+    pub fade_in_s: f64,
+    /// Noir à insérer avant ce fond (s), pour un intervalle vide de la timeline
+    pub gap_before_s: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(trim_in_ms: Option<i32>, trim_out_ms: Option<i32>, speed: f64) -> BackgroundClip {
+        BackgroundClip {
+            trim_in_ms,
+            trim_out_ms,
+            speed,
+            ..BackgroundClip::from(BackgroundSource::Path("bg.mp4".to_string()))
+        }
+    }
+
+    #[test]
+    fn trim_range_defaults_to_the_whole_source() {
+        assert_eq!(clip(None, None, 1.0).trim_range_ms(8000), (0, 8000));
+        assert_eq!(clip(Some(1000), Some(5000), 1.0).trim_range_ms(8000), (1000, 5000));
+    }
+
+    #[test]
+    fn trim_range_is_bounded_to_the_source() {
+        assert_eq!(clip(Some(-500), Some(20_000), 1.0).trim_range_ms(8000), (0, 8000));
+        assert_eq!(clip(Some(9000), None, 1.0).trim_range_ms(8000), (8000, 8000));
+        assert_eq!(clip(Some(4000), Some(2000), 1.0).trim_range_ms(8000), (4000, 4000));
+    }
+
+    #[test]
+    fn timeline_length_accounts_for_speed() {
+        assert_eq!(clip(Some(1000), Some(5000), 2.0).timeline_len_ms(8000), 2000);
+        assert_eq!(clip(None, None, 0.5).timeline_len_ms(3000), 6000);
+        // Vitesse invalide : lecture normale
+        assert_eq!(clip(None, None, 0.0).timeline_len_ms(3000), 3000);
+        assert_eq!(clip(None, None, f64::NAN).timeline_len_ms(3000), 3000);
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::fs;
+use std::process::Command;
+
+use crate::encoders;
+use crate::exporter::{self, VideoCodec};
+
+/// Arguments d'un encodeur, au format de `choose_best_codec` : (paramètres, extra).
+///
+/// Clés de `extra` : `preset`, et `hwupload` pour les filtres à ajouter en fin de chaîne
+/// vidéo quand l'encodeur attend des surfaces matérielles (VAAPI).
+pub(crate) type EncoderArgs = (Vec<String>, HashMap<String, Option<String>>);
+
+/// Backend d'encodage matériel : sait nommer ses encodeurs, les tester et construire leurs arguments
+pub(crate) trait EncoderBackend: Sync {
+    /// Nom court du backend (nvenc, qsv, ...)
+    fn name(&self) -> &'static str;
+
+    /// Encodeur FFmpeg pour une famille de codec, s'il existe
+    fn encoder(&self, codec: VideoCodec) -> Option<&'static str>;
+
+    /// Faux si le backend ne peut pas exister sur cette plateforme
+    fn supported_platform(&self) -> bool {
+        true
+    }
+
+    /// Périphériques candidats ; `None` quand le backend choisit seul son périphérique
+    fn devices(&self) -> Vec<Option<String>> {
+        vec![None]
+    }
+
+    /// Vrai si l'export peut imposer le périphérique (`OutputFormat::hw_device`)
+    fn selectable_device(&self) -> bool {
+        false
+    }
+
+    fn encoder_args(&self, encoder: &str, device: Option<&str>) -> EncoderArgs;
+
+    /// Messages FFmpeg propres à ce backend qui signalent une panne de l'encodeur
+    fn failure_markers(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Encode une image de test ; renvoie le message d'erreur de FFmpeg en cas d'échec
+    fn test_encode(&self, ffmpeg: &str, encoder: &str, device: Option<&str>, size: (u32, u32)) -> Result<(), String> {
+        run_test_encode(ffmpeg, encoder, self.encoder_args(encoder, device), size)
+    }
+}
+
+fn to_strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+fn extra_with_preset(preset: Option<&str>) -> HashMap<String, Option<String>> {
+    let mut extra = HashMap::new();
+    extra.insert("preset".to_string(), preset.map(|p| p.to_string()));
+    extra
+}
+
+fn run_test_encode(ffmpeg: &str, encoder: &str, (params, extra): EncoderArgs, (w, h): (u32, u32)) -> Result<(), String> {
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args([
+        "-y", "-hide_banner", "-loglevel", "error",
+        "-f", "lavfi", "-i", &format!("color=c=black:s={}x{}:r=1:d=0.04", w, h),
+    ]);
+    if let Some(Some(upload)) = extra.get("hwupload") {
+        cmd.args(["-vf", upload]);
+    }
+    cmd.args(["-c:v", encoder]);
+    if let Some(Some(preset)) = extra.get("preset") {
+        cmd.args(["-preset", preset]);
+    }
+    cmd.args(&params);
+    cmd.args(["-frames:v", "1", "-f", "null", "-"]);
+
+    exporter::configure_command_no_window(&mut cmd);
+
+    let output = cmd.output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+struct Nvenc;
+
+impl EncoderBackend for Nvenc {
+    fn name(&self) -> &'static str {
+        "nvenc"
+    }
+
+    fn encoder(&self, codec: VideoCodec) -> Option<&'static str> {
+        match codec {
+            VideoCodec::H264 => Some("h264_nvenc"),
+            VideoCodec::Hevc => Some("hevc_nvenc"),
+            VideoCodec::Av1 => Some("av1_nvenc"),
+            VideoCodec::Vp9 => None,
+        }
+    }
+
+    fn supported_platform(&self) -> bool {
+        !cfg!(target_os = "macos")
+    }
+
+    fn encoder_args(&self, encoder: &str, _device: Option<&str>) -> EncoderArgs {
+        // av1_nvenc n'accepte que les presets p1..p7
+        let preset = if encoder.starts_with("av1") { "p5" } else { "fast" };
+        (to_strings(&["-pix_fmt", "yuv420p"]), extra_with_preset(Some(preset)))
+    }
+
+    fn failure_markers(&self) -> &'static [&'static str] {
+        &[
+            "openencodesessionex failed",
+            "incompatible client key",
+            "no capable devices found",
+            "cuda_error",
+            "cannot load libnvidia-encode",
+            "cannot load nvencodeapi",
+        ]
+    }
+
+    fn test_encode(&self, ffmpeg: &str, encoder: &str, device: Option<&str>, size: (u32, u32)) -> Result<(), String> {
+        let result = run_test_encode(ffmpeg, encoder, self.encoder_args(encoder, device), size);
+
+        // Distinguer "pas de GPU NVIDIA" d'une vraie erreur de configuration dans les logs
+        if let Err(ref stderr) = result {
+            let stderr_lower = stderr.to_lowercase();
+            if stderr_lower.contains("cannot load nvcuda.dll")
+                || stderr_lower.contains("no nvidia devices")
+                || stderr_lower.contains("cuda")
+                || stderr_lower.contains("driver")
+            {
+                println!("[nvenc_test] ✗ NVENC non disponible (pas de GPU NVIDIA ou drivers manquants)");
+            } else {
+                println!("[nvenc_test] ✗ NVENC erreur: {}", stderr);
+            }
+        }
+        result
+    }
+}
+
+struct Qsv;
+
+impl EncoderBackend for Qsv {
+    fn name(&self) -> &'static str {
+        "qsv"
+    }
+
+    fn encoder(&self, codec: VideoCodec) -> Option<&'static str> {
+        match codec {
+            VideoCodec::H264 => Some("h264_qsv"),
+            VideoCodec::Hevc => Some("hevc_qsv"),
+            VideoCodec::Av1 => Some("av1_qsv"),
+            VideoCodec::Vp9 => Some("vp9_qsv"),
+        }
+    }
+
+    fn supported_platform(&self) -> bool {
+        !cfg!(target_os = "macos")
+    }
+
+    fn encoder_args(&self, _encoder: &str, _device: Option<&str>) -> EncoderArgs {
+        // QSV n'accepte pas yuv420p, seulement nv12
+        (to_strings(&["-pix_fmt", "nv12"]), extra_with_preset(Some("veryfast")))
+    }
+
+    fn failure_markers(&self) -> &'static [&'static str] {
+        &["error initializing an internal mfx session", "mfx_err_device_failed", "mfx_err_memory_alloc"]
+    }
+}
+
+struct Amf;
+
+impl EncoderBackend for Amf {
+    fn name(&self) -> &'static str {
+        "amf"
+    }
+
+    fn encoder(&self, codec: VideoCodec) -> Option<&'static str> {
+        match codec {
+            VideoCodec::H264 => Some("h264_amf"),
+            VideoCodec::Hevc => Some("hevc_amf"),
+            VideoCodec::Av1 => Some("av1_amf"),
+            VideoCodec::Vp9 => None,
+        }
+    }
+
+    fn supported_platform(&self) -> bool {
+        !cfg!(target_os = "macos")
+    }
+
+    fn encoder_args(&self, _encoder: &str, _device: Option<&str>) -> EncoderArgs {
+        (to_strings(&["-pix_fmt", "yuv420p", "-quality", "speed"]), extra_with_preset(None))
+    }
+
+    fn failure_markers(&self) -> &'static [&'static str] {
+        &["amf_fail", "failed to initialize amf"]
+    }
+}
+
+struct Vaapi;
+
+impl EncoderBackend for Vaapi {
+    fn name(&self) -> &'static str {
+        "vaapi"
+    }
+
+    fn encoder(&self, codec: VideoCodec) -> Option<&'static str> {
+        match codec {
+            VideoCodec::H264 => Some("h264_vaapi"),
+            VideoCodec::Hevc => Some("hevc_vaapi"),
+            VideoCodec::Av1 => Some("av1_vaapi"),
+            VideoCodec::Vp9 => Some("vp9_vaapi"),
+        }
+    }
+
+    fn supported_platform(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    /// `VAAPI_DEVICE` force un périphérique, sinon tous les /dev/dri/renderD* sont essayés
+    fn devices(&self) -> Vec<Option<String>> {
+        if let Ok(device) = std::env::var("VAAPI_DEVICE") {
+            return vec![Some(device)];
+        }
+
+        let mut devices: Vec<String> = fs::read_dir("/dev/dri")
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.file_name().and_then(|n| n.to_str()).map(|n| n.starts_with("renderD")).unwrap_or(false))
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        devices.sort();
+        devices.into_iter().map(Some).collect()
+    }
+
+    fn selectable_device(&self) -> bool {
+        true
+    }
+
+    fn encoder_args(&self, _encoder: &str, device: Option<&str>) -> EncoderArgs {
+        let device = device.unwrap_or("/dev/dri/renderD128");
+        let params = vec![
+            "-init_hw_device".to_string(), format!("vaapi=va:{}", device),
+            "-filter_hw_device".to_string(), "va".to_string(),
+        ];
+        let mut extra = extra_with_preset(None);
+        // Les encodeurs VAAPI attendent des surfaces GPU : conversion nv12 puis upload
+        extra.insert("hwupload".to_string(), Some("format=nv12,hwupload".to_string()));
+        (params, extra)
+    }
+
+    fn failure_markers(&self) -> &'static [&'static str] {
+        &["failed to initialise vaapi connection", "no va display found", "failed to upload frame", "failed to sync surface"]
+    }
+}
+
+struct VideoToolbox;
+
+impl EncoderBackend for VideoToolbox {
+    fn name(&self) -> &'static str {
+        "videotoolbox"
+    }
+
+    fn encoder(&self, codec: VideoCodec) -> Option<&'static str> {
+        match codec {
+            VideoCodec::H264 => Some("h264_videotoolbox"),
+            VideoCodec::Hevc => Some("hevc_videotoolbox"),
+            VideoCodec::Av1 | VideoCodec::Vp9 => None,
+        }
+    }
+
+    fn supported_platform(&self) -> bool {
+        cfg!(target_os = "macos")
+    }
+
+    fn encoder_args(&self, _encoder: &str, _device: Option<&str>) -> EncoderArgs {
+        // allow_sw 0 : échouer plutôt que basculer silencieusement sur l'encodeur logiciel d'Apple
+        (to_strings(&["-pix_fmt", "yuv420p", "-allow_sw", "0", "-realtime", "1"]), extra_with_preset(None))
+    }
+
+    fn failure_markers(&self) -> &'static [&'static str] {
+        &["vtcompressionsessioncreate", "vtcompressionsessionencodeframe", "hardware encoder may be busy"]
+    }
+}
+
+// Backends par ordre de préférence
+static BACKENDS: [&dyn EncoderBackend; 5] = [&Nvenc, &Qsv, &Amf, &Vaapi, &VideoToolbox];
+
+/// Encodeurs matériels connus pour une famille, par ordre de préférence
+pub(crate) fn hw_encoders(codec: VideoCodec) -> Vec<&'static str> {
+    BACKENDS.iter().filter_map(|b| b.encoder(codec)).collect()
+}
+
+fn backend_for(encoder: &str) -> Option<&'static dyn EncoderBackend> {
+    BACKENDS
+        .iter()
+        .copied()
+        .find(|b| VideoCodec::ALL.iter().any(|&c| b.encoder(c) == Some(encoder)))
+}
+
+/// Vrai si l'encodeur appartient à un backend matériel
+pub(crate) fn is_hw_encoder(encoder: &str) -> bool {
+    backend_for(encoder).is_some()
+}
+
+// Clé du cache de test : l'encodeur, suivi du périphérique s'il y en a un
+fn test_key(encoder: &str, device: Option<&str>) -> String {
+    match device {
+        Some(device) => format!("{}@{}", encoder, device),
+        None => encoder.to_string(),
+    }
+}
+
+/// Premier encodeur matériel fonctionnel pour la famille, avec ses arguments.
+///
+/// `device` remplace les périphériques essayés par les backends qui le permettent (VAAPI).
+/// Le résultat des tests est mis en cache par binaire FFmpeg (voir `encoders`).
+pub(crate) fn find_working_encoder(codec: VideoCodec, device: Option<&str>) -> Option<(&'static str, EncoderArgs)> {
+    for backend in BACKENDS.iter().filter(|b| b.supported_platform()) {
+        let Some(encoder) = backend.encoder(codec) else {
+            continue;
+        };
+        if !encoders::is_encoder_available(encoder) {
+            continue;
+        }
+
+        let devices = match device {
+            Some(device) if backend.selectable_device() => vec![Some(device.to_string())],
+            _ => backend.devices(),
+        };
+        for device in devices {
+            let device = device.as_deref();
+            let ok = encoders::cached_encoder_test(&test_key(encoder, device), |ffmpeg| {
+                println!("[{}] Test de {} {}...", backend.name(), encoder, device.unwrap_or(""));
+                backend.test_encode(ffmpeg, encoder, device, (256, 256)).is_ok()
+            });
+            if ok {
+                return Some((encoder, backend.encoder_args(encoder, device)));
+            }
+        }
+        println!("[codec] {} détecté mais non fonctionnel", encoder);
+    }
+    None
+}
+
+/// Vrai si le stderr d'un export échoué montre une panne de l'encodeur matériel
+/// (reset du driver, plus de sessions disponibles...) plutôt qu'une erreur d'entrée.
+///
+/// Seuls les messages de périphérique ou de session propres au backend comptent : un
+/// "Error while opening encoder" générique peut venir d'un mauvais paramètre, qu'un
+/// nouvel essai en logiciel ne corrigerait pas.
+pub(crate) fn is_encoder_failure(encoder: &str, stderr: &str) -> bool {
+    let Some(backend) = backend_for(encoder) else {
+        return false;
+    };
+    let stderr = stderr.to_lowercase();
+    backend.failure_markers().iter().any(|m| stderr.contains(m))
+}
+
+/// Encodage de test via le backend de l'encodeur (premier périphérique fonctionnel),
+/// ou avec des arguments génériques pour les encodeurs logiciels
+pub(crate) fn test_encoder(ffmpeg: &str, encoder: &str, size: (u32, u32)) -> Result<(), String> {
+    let Some(backend) = backend_for(encoder) else {
+        return run_test_encode(ffmpeg, encoder, (to_strings(&["-pix_fmt", "yuv420p"]), HashMap::new()), size);
+    };
+
+    let mut last_error = format!("Aucun périphérique {} trouvé", backend.name());
+    for device in backend.devices() {
+        match backend.test_encode(ffmpeg, encoder, device.as_deref(), size) {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
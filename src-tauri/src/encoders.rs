@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+use tauri::Manager;
+use tokio::task;
+
+use crate::encoder_backend;
+use crate::exporter::{self, ExportError, VideoCodec};
+
+// Fichier de cache de la détection, dans le dossier app data
+static CACHE_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+lazy_static::lazy_static! {
+    static ref DETECTION_CACHE: Mutex<Option<DetectionCache>> = Mutex::new(None);
+}
+
+/// Résultat de la détection des encodeurs, valable tant que le binaire FFmpeg ne change pas
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+struct DetectionCache {
+    ffmpeg_path: String,
+    ffmpeg_mtime: u64,
+    /// Noms des encodeurs compilés dans FFmpeg (`ffmpeg -encoders`)
+    encoders: Option<Vec<String>>,
+    /// Résultat des encodages de test, par encodeur. Seuls les succès sont enregistrés sur disque :
+    /// un échec (driver pas encore chargé, GPU occupé...) est retesté au prochain lancement.
+    tests: HashMap<String, bool>,
+    /// Résultat complet de `get_encoder_capabilities` (tests de chaque encodeur et résolutions)
+    #[serde(default)]
+    capabilities: Option<EncoderCapabilities>,
+}
+
+// Résolutions testées pour estimer la limite des encodeurs matériels
+const RESOLUTION_STEPS: [(u32, u32); 3] = [(1920, 1080), (3840, 2160), (7680, 4320)];
+
+// Encodeurs audio utilisés par l'export
+const AUDIO_CANDIDATES: [&str; 3] = ["aac", "libopus", "libmp3lame"];
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EncoderInfo {
+    pub name: String,
+    pub description: String,
+    pub hardware: bool,
+    /// Vrai si l'encodeur fait partie de ceux que l'export peut choisir (testé)
+    pub candidate: bool,
+    pub pixel_formats: Vec<String>,
+    /// Plus grande résolution encodée avec succès (encodeurs matériels uniquement)
+    pub max_resolution: Option<(u32, u32)>,
+    /// Résultat de l'encodage de test, `None` si l'encodeur n'a pas été testé
+    pub test_passed: Option<bool>,
+    pub test_error: Option<String>,
+}
+
+/// Encodeur retenu pour une famille de codec, avec la raison du choix
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SelectedEncoder {
+    pub codec: VideoCodec,
+    pub encoder: Option<String>,
+    pub hardware: bool,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EncoderCapabilities {
+    pub ffmpeg_path: String,
+    pub ffmpeg_version: Option<String>,
+    pub build_configuration: Vec<String>,
+    pub video_encoders: Vec<EncoderInfo>,
+    pub audio_encoders: Vec<EncoderInfo>,
+    pub selected: Vec<SelectedEncoder>,
+}
+
+fn run_ffmpeg(ffmpeg: &str, args: &[&str]) -> Result<std::process::Output, ExportError> {
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(args);
+    exporter::configure_command_no_window(&mut cmd);
+    Ok(cmd.output()?)
+}
+
+fn is_video_candidate(name: &str) -> bool {
+    VideoCodec::ALL
+        .iter()
+        .any(|c| c.hw_encoders().contains(&name) || c.software_encoders().contains(&name))
+}
+
+/// Version et options de compilation (`ffmpeg -version`)
+fn ffmpeg_build_info(ffmpeg: &str) -> (Option<String>, Vec<String>) {
+    let Ok(output) = run_ffmpeg(ffmpeg, &["-hide_banner", "-version"]) else {
+        return (None, Vec::new());
+    };
+    let txt = String::from_utf8_lossy(&output.stdout);
+
+    let version = txt
+        .lines()
+        .next()
+        .and_then(|l| l.strip_prefix("ffmpeg version "))
+        .and_then(|l| l.split_whitespace().next())
+        .map(|v| v.to_string());
+
+    let configuration = txt
+        .lines()
+        .find_map(|l| l.strip_prefix("configuration:"))
+        .map(|l| l.split_whitespace().map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+
+    (version, configuration)
+}
+
+/// Liste `ffmpeg -encoders` : (type V/A, nom, description)
+fn list_encoders(ffmpeg: &str) -> Result<Vec<(char, String, String)>, ExportError> {
+    let output = run_ffmpeg(ffmpeg, &["-hide_banner", "-encoders"])?;
+    let txt = String::from_utf8_lossy(&output.stdout);
+
+    // Les lignes d'encodeurs suivent le séparateur " ------"
+    let encoders = txt
+        .lines()
+        .skip_while(|l| !l.trim_start().starts_with("------"))
+        .skip(1)
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let flags = parts.next()?;
+            let name = parts.next()?;
+            let description = parts.collect::<Vec<_>>().join(" ");
+            Some((flags.chars().next()?, name.to_string(), description))
+        })
+        .collect();
+
+    Ok(encoders)
+}
+
+/// Formats de pixels supportés (`ffmpeg -h encoder=<nom>`)
+fn encoder_pixel_formats(ffmpeg: &str, encoder: &str) -> Vec<String> {
+    let Ok(output) = run_ffmpeg(ffmpeg, &["-hide_banner", "-h", &format!("encoder={}", encoder)]) else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Supported pixel formats:"))
+        .map(|l| l.split_whitespace().map(|s| s.to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn test_audio_encode(ffmpeg: &str, encoder: &str) -> Result<(), String> {
+    let output = run_ffmpeg(ffmpeg, &[
+        "-y", "-hide_banner", "-loglevel", "error",
+        "-f", "lavfi", "-i", "anullsrc=r=48000:cl=stereo",
+        "-t", "0.1",
+        "-c:a", encoder,
+        "-f", "null", "-",
+    ])
+    .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn probe_video_encoder(ffmpeg: &str, name: String, description: String) -> EncoderInfo {
+    let hardware = encoder_backend::is_hw_encoder(&name);
+    let candidate = is_video_candidate(&name);
+    let mut info = EncoderInfo {
+        pixel_formats: if candidate { encoder_pixel_formats(ffmpeg, &name) } else { Vec::new() },
+        name,
+        description,
+        hardware,
+        candidate,
+        max_resolution: None,
+        test_passed: None,
+        test_error: None,
+    };
+    if !candidate {
+        return info;
+    }
+
+    // 256x256 : taille minimale acceptée par tous les encodeurs matériels
+    match encoder_backend::test_encoder(ffmpeg, &info.name, (256, 256)) {
+        Ok(()) => info.test_passed = Some(true),
+        Err(e) => {
+            println!("[encoders] ✗ {}: {}", info.name, e);
+            info.test_passed = Some(false);
+            info.test_error = Some(e);
+            return info;
+        }
+    }
+
+    if hardware {
+        for size in RESOLUTION_STEPS {
+            if encoder_backend::test_encoder(ffmpeg, &info.name, size).is_err() {
+                break;
+            }
+            info.max_resolution = Some(size);
+        }
+    }
+
+    println!("[encoders] ✓ {} (max {:?})", info.name, info.max_resolution);
+    info
+}
+
+fn probe_audio_encoder(ffmpeg: &str, name: String, description: String) -> EncoderInfo {
+    let candidate = AUDIO_CANDIDATES.contains(&name.as_str());
+    let test = if candidate { Some(test_audio_encode(ffmpeg, &name)) } else { None };
+    EncoderInfo {
+        name,
+        description,
+        hardware: false,
+        candidate,
+        pixel_formats: Vec::new(),
+        max_resolution: None,
+        test_passed: test.as_ref().map(|t| t.is_ok()),
+        test_error: test.and_then(|t| t.err()),
+    }
+}
+
+/// Reproduit l'ordre de préférence de l'export : matériel fonctionnel d'abord, puis logiciel
+fn select_encoders(video: &[EncoderInfo]) -> Vec<SelectedEncoder> {
+    let passed = |name: &str| video.iter().any(|e| e.name == name && e.test_passed == Some(true));
+
+    VideoCodec::ALL
+        .iter()
+        .map(|&codec| {
+            if let Some(hw) = codec.hw_encoders().iter().find(|n| passed(n)) {
+                return SelectedEncoder {
+                    codec,
+                    encoder: Some(hw.to_string()),
+                    hardware: true,
+                    reason: "Encodeur matériel disponible et test d'encodage réussi".to_string(),
+                };
+            }
+            let failed_hw: Vec<&str> = codec
+                .hw_encoders()
+                .iter()
+                .filter(|n| video.iter().any(|e| &e.name == *n))
+                .copied()
+                .collect();
+            match codec.software_encoders().iter().find(|n| passed(n)) {
+                Some(sw) => SelectedEncoder {
+                    codec,
+                    encoder: Some(sw.to_string()),
+                    hardware: false,
+                    reason: if failed_hw.is_empty() {
+                        "Aucun encodeur matériel dans ce build FFmpeg".to_string()
+                    } else {
+                        format!("Test d'encodage échoué pour {}", failed_hw.join(", "))
+                    },
+                },
+                None => SelectedEncoder {
+                    codec,
+                    encoder: None,
+                    hardware: false,
+                    reason: "Aucun encodeur utilisable pour ce codec".to_string(),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Capacités des encodeurs, détectées une fois par binaire FFmpeg puis lues dans le cache.
+///
+/// Les encodages de test tournent hors du verrou du cache.
+pub(crate) fn detect_encoder_capabilities() -> Result<EncoderCapabilities, ExportError> {
+    if let Some(capabilities) = with_cache(|cache| cache.capabilities.clone()).flatten() {
+        return Ok(capabilities);
+    }
+
+    let capabilities = probe_encoder_capabilities()?;
+    with_cache(|cache| {
+        cache.capabilities = Some(capabilities.clone());
+        save_cache_file(cache);
+    });
+    Ok(capabilities)
+}
+
+fn probe_encoder_capabilities() -> Result<EncoderCapabilities, ExportError> {
+    let ffmpeg = exporter::ffmpeg_binary()?;
+    let (ffmpeg_version, build_configuration) = ffmpeg_build_info(&ffmpeg);
+
+    let mut video_encoders = Vec::new();
+    let mut audio_encoders = Vec::new();
+    for (kind, name, description) in list_encoders(&ffmpeg)? {
+        match kind {
+            'V' => video_encoders.push(probe_video_encoder(&ffmpeg, name, description)),
+            'A' => audio_encoders.push(probe_audio_encoder(&ffmpeg, name, description)),
+            _ => {}
+        }
+    }
+
+    let selected = select_encoders(&video_encoders);
+
+    Ok(EncoderCapabilities {
+        ffmpeg_path: ffmpeg,
+        ffmpeg_version,
+        build_configuration,
+        video_encoders,
+        audio_encoders,
+        selected,
+    })
+}
+
+/// Chemin et date de modification du binaire FFmpeg utilisé, qui servent de clé au cache
+fn ffmpeg_key() -> Option<(String, u64)> {
+    let path = exporter::ffmpeg_binary().ok()?;
+    let mtime = fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Some((path, mtime))
+}
+
+fn load_cache_file(path: &str, mtime: u64) -> Option<DetectionCache> {
+    let content = fs::read_to_string(CACHE_PATH.get()?).ok()?;
+    let cache: DetectionCache = serde_json::from_str(&content).ok()?;
+    (cache.ffmpeg_path == path && cache.ffmpeg_mtime == mtime).then_some(cache)
+}
+
+fn save_cache_file(cache: &DetectionCache) {
+    let Some(path) = CACHE_PATH.get() else {
+        return;
+    };
+    // Les capacités ne sont gardées sur disque que si aucun encodage de test n'a échoué
+    let capabilities = cache.capabilities.clone().filter(|c| {
+        c.video_encoders.iter().chain(&c.audio_encoders).all(|e| e.test_passed != Some(false))
+    });
+    let persisted = DetectionCache {
+        tests: cache.tests.iter().filter(|(_, &ok)| ok).map(|(k, &ok)| (k.clone(), ok)).collect(),
+        capabilities,
+        ..cache.clone()
+    };
+    match serde_json::to_string_pretty(&persisted) {
+        Ok(content) => {
+            if let Err(e) = fs::write(path, content) {
+                println!("[encoders] Impossible d'enregistrer le cache: {}", e);
+            }
+        }
+        Err(e) => println!("[encoders] Erreur sérialisation cache: {}", e),
+    }
+}
+
+/// Donne accès au cache de détection du binaire FFmpeg courant, rechargé depuis le disque si besoin.
+///
+/// Le verrou est gardé pendant `f` : aucun FFmpeg ne doit y être lancé.
+fn with_cache<T>(f: impl FnOnce(&mut DetectionCache) -> T) -> Option<T> {
+    let (path, mtime) = ffmpeg_key()?;
+    let mut guard = DETECTION_CACHE.lock().ok()?;
+
+    let stale = guard
+        .as_ref()
+        .map(|c| c.ffmpeg_path != path || c.ffmpeg_mtime != mtime)
+        .unwrap_or(true);
+    if stale {
+        *guard = Some(load_cache_file(&path, mtime).unwrap_or_else(|| {
+            println!("[encoders] Nouvelle détection pour {}", path);
+            DetectionCache { ffmpeg_path: path.clone(), ffmpeg_mtime: mtime, ..Default::default() }
+        }));
+    }
+
+    guard.as_mut().map(f)
+}
+
+/// Vrai si l'encodeur est compilé dans le binaire FFmpeg (liste mise en cache)
+pub(crate) fn is_encoder_available(name: &str) -> bool {
+    let cached = with_cache(|cache| cache.encoders.as_ref().map(|e| e.iter().any(|n| n == name)));
+    if let Some(available) = cached.flatten() {
+        return available;
+    }
+
+    // `ffmpeg -encoders` est lancé hors du verrou
+    let Some((ffmpeg, _)) = ffmpeg_key() else {
+        return false;
+    };
+    let names: Vec<String> = match list_encoders(&ffmpeg) {
+        Ok(list) => list.into_iter().map(|(_, name, _)| name).collect(),
+        Err(e) => {
+            println!("[encoders] Liste des encodeurs illisible: {}", e);
+            return false;
+        }
+    };
+    let available = names.iter().any(|n| n == name);
+    with_cache(|cache| {
+        cache.encoders = Some(names);
+        save_cache_file(cache);
+    });
+    available
+}
+
+/// Résultat mis en cache d'un encodage de test ; `test` n'est lancé qu'une fois par binaire FFmpeg.
+///
+/// Le test tourne hors du verrou : deux chunks parallèles peuvent le lancer chacun, sans bloquer
+/// les autres accès au cache.
+pub(crate) fn cached_encoder_test(encoder: &str, test: impl FnOnce(&str) -> bool) -> bool {
+    if let Some(ok) = with_cache(|cache| cache.tests.get(encoder).copied()).flatten() {
+        return ok;
+    }
+
+    let Some((ffmpeg, _)) = ffmpeg_key() else {
+        return false;
+    };
+    let ok = test(&ffmpeg);
+    with_cache(|cache| {
+        cache.tests.insert(encoder.to_string(), ok);
+        if ok {
+            save_cache_file(cache);
+        }
+    });
+    ok
+}
+
+/// Indique où enregistrer le cache de détection
+pub fn init(app: &tauri::AppHandle) {
+    match app.path().app_data_dir() {
+        Ok(dir) => {
+            let _ = fs::create_dir_all(&dir);
+            let _ = CACHE_PATH.set(dir.join("encoder-cache.json"));
+        }
+        Err(e) => println!("[encoders] Dossier app data introuvable, cache en mémoire uniquement: {}", e),
+    }
+}
+
+/// Vide le cache de détection (liste, tests et capacités des encodeurs), en mémoire et sur disque
+#[tauri::command]
+pub fn invalidate_encoder_cache() -> Result<(), ExportError> {
+    if let Ok(mut cache) = DETECTION_CACHE.lock() {
+        *cache = None;
+    }
+    if let Some(path) = CACHE_PATH.get() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    println!("[encoders] Cache de détection vidé");
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_encoder_capabilities() -> Result<EncoderCapabilities, ExportError> {
+    task::spawn_blocking(detect_encoder_capabilities)
+        .await
+        .map_err(|e| ExportError::io(format!("Erreur tâche: {}", e)))?
+}
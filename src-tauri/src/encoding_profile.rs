@@ -0,0 +1,413 @@
+use std::path::Path;
+
+use crate::exporter::ExportError;
+
+/// Contrôle du débit vidéo.
+///
+/// La valeur CRF est exprimée sur l'échelle de libx264 (0-51) puis convertie pour les autres encodeurs.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum RateControl {
+    Crf { value: u32 },
+    Bitrate {
+        kbps: u32,
+        #[serde(default)]
+        max_kbps: Option<u32>,
+    },
+    /// Taille maximale du fichier (en Mo) : le débit est calculé d'après la durée exportée
+    TargetSize { max_mb: f64 },
+}
+
+/// Réglages d'encodage passés à `export_video`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EncodingProfile {
+    pub rate_control: RateControl,
+    /// Preset x264/x265 (ultrafast ... veryslow), converti pour SVT-AV1 et NVENC
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub two_pass: bool,
+    /// Intervalle entre deux images clés, en secondes
+    #[serde(default)]
+    pub keyframe_interval_s: Option<f64>,
+    #[serde(default = "default_audio_bitrate")]
+    pub audio_bitrate_kbps: u32,
+}
+
+fn default_audio_bitrate() -> u32 {
+    192
+}
+
+/// Presets intégrés proposés dans l'interface
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingPreset {
+    Draft,
+    #[serde(rename = "youtube_1080p")]
+    Youtube1080p,
+    Archive,
+    Whatsapp,
+}
+
+impl EncodingPreset {
+    const ALL: [EncodingPreset; 4] = [
+        EncodingPreset::Draft,
+        EncodingPreset::Youtube1080p,
+        EncodingPreset::Archive,
+        EncodingPreset::Whatsapp,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            EncodingPreset::Draft => "Draft",
+            EncodingPreset::Youtube1080p => "YouTube 1080p",
+            EncodingPreset::Archive => "Archive",
+            EncodingPreset::Whatsapp => "WhatsApp ≤16 MB",
+        }
+    }
+
+    pub fn profile(self) -> EncodingProfile {
+        match self {
+            EncodingPreset::Draft => EncodingProfile {
+                rate_control: RateControl::Crf { value: 28 },
+                preset: Some("ultrafast".to_string()),
+                two_pass: false,
+                keyframe_interval_s: None,
+                audio_bitrate_kbps: 128,
+            },
+            EncodingPreset::Youtube1080p => EncodingProfile {
+                rate_control: RateControl::Crf { value: 18 },
+                preset: Some("medium".to_string()),
+                two_pass: false,
+                keyframe_interval_s: Some(2.0),
+                audio_bitrate_kbps: 192,
+            },
+            EncodingPreset::Archive => EncodingProfile {
+                rate_control: RateControl::Crf { value: 16 },
+                preset: Some("slow".to_string()),
+                two_pass: false,
+                keyframe_interval_s: None,
+                audio_bitrate_kbps: 256,
+            },
+            EncodingPreset::Whatsapp => EncodingProfile {
+                rate_control: RateControl::TargetSize { max_mb: 16.0 },
+                preset: Some("medium".to_string()),
+                two_pass: true,
+                keyframe_interval_s: Some(2.0),
+                audio_bitrate_kbps: 96,
+            },
+        }
+    }
+}
+
+/// Profil reçu du frontend : soit le nom d'un preset (`"youtube_1080p"`), soit un profil complet
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum EncodingProfileChoice {
+    Preset(EncodingPreset),
+    Custom(EncodingProfile),
+}
+
+impl EncodingProfileChoice {
+    pub fn resolve(&self) -> EncodingProfile {
+        match self {
+            EncodingProfileChoice::Preset(preset) => preset.profile(),
+            EncodingProfileChoice::Custom(profile) => profile.clone(),
+        }
+    }
+}
+
+fn is_software_x26x(encoder: &str) -> bool {
+    encoder == "libx264" || encoder == "libx265"
+}
+
+/// Encodeurs capables d'un vrai double passage (FFmpeg lancé deux fois avec `-pass`)
+pub(crate) fn supports_two_runs(encoder: &str) -> bool {
+    matches!(encoder, "libx264" | "libx265" | "libvpx-vp9" | "libaom-av1")
+}
+
+// Marge pour l'en-tête du conteneur et les écarts de l'encodeur
+const TARGET_SIZE_MARGIN: f64 = 0.96;
+
+// En dessous, l'image (ou le son) n'est plus exploitable
+const MIN_VIDEO_KBPS: f64 = 100.0;
+const MIN_AUDIO_KBPS: f64 = 32.0;
+
+impl EncodingProfile {
+    pub(crate) fn target_size_mb(&self) -> Option<f64> {
+        match self.rate_control {
+            RateControl::TargetSize { max_mb } => Some(max_mb),
+            _ => None,
+        }
+    }
+
+    /// Convertit une taille cible en débit pour la durée exportée : débit vidéo encodé en deux passes,
+    /// ou débit audio pour un export audio seul.
+    ///
+    /// Erreur si la taille est trop petite pour la durée, plutôt que de la dépasser.
+    pub(crate) fn for_duration(&self, duration_s: f64, has_audio: bool, audio_only: bool) -> Result<EncodingProfile, ExportError> {
+        let Some(max_mb) = self.target_size_mb() else {
+            return Ok(self.clone());
+        };
+
+        let duration_s = duration_s.max(0.001);
+        let total_kbps = max_mb * 1024.0 * 1024.0 * 8.0 / 1000.0 * TARGET_SIZE_MARGIN / duration_s;
+        // Taille nécessaire pour un débit total donné
+        let min_mb = |kbps: f64| kbps * duration_s * 1000.0 / 8.0 / (1024.0 * 1024.0) / TARGET_SIZE_MARGIN;
+
+        if audio_only {
+            if total_kbps < MIN_AUDIO_KBPS {
+                return Err(ExportError::invalid_input(format!(
+                    "Taille cible de {:.1} Mo trop petite pour {:.0}s d'audio (minimum {:.1} Mo)",
+                    max_mb, duration_s, min_mb(MIN_AUDIO_KBPS)
+                )));
+            }
+            let kbps = (total_kbps as u32).min(self.audio_bitrate_kbps);
+            println!("[codec] Taille cible {:.1} Mo sur {:.1}s -> audio {} kb/s", max_mb, duration_s, kbps);
+            return Ok(EncodingProfile { audio_bitrate_kbps: kbps, ..self.clone() });
+        }
+
+        let audio_kbps = if has_audio { self.audio_bitrate_kbps as f64 } else { 0.0 };
+        let video_kbps = total_kbps - audio_kbps;
+        if video_kbps < MIN_VIDEO_KBPS {
+            return Err(ExportError::invalid_input(format!(
+                "Taille cible de {:.1} Mo trop petite pour {:.0}s de vidéo (minimum {:.1} Mo avec l'audio à {} kb/s)",
+                max_mb, duration_s, min_mb(MIN_VIDEO_KBPS + audio_kbps), audio_kbps
+            )));
+        }
+        let kbps = video_kbps as u32;
+        println!(
+            "[codec] Taille cible {:.1} Mo sur {:.1}s -> vidéo {} kb/s (audio {} kb/s)",
+            max_mb, duration_s, kbps, audio_kbps
+        );
+
+        Ok(EncodingProfile {
+            rate_control: RateControl::Bitrate { kbps, max_kbps: Some(kbps) },
+            two_pass: true,
+            ..self.clone()
+        })
+    }
+
+    /// CRF converti vers l'échelle de l'encodeur
+    fn crf_for(encoder: &str, value: u32) -> u32 {
+        match encoder {
+            // x265 donne une qualité comparable à x264 avec un CRF ~5 points plus haut
+            "libx265" => (value + 5).min(51),
+            // VP9 / AV1 utilisent une échelle 0-63
+            "libvpx-vp9" | "libaom-av1" | "libsvtav1" => ((value as f64 * 1.4).round() as u32).min(63),
+            _ => value.min(51),
+        }
+    }
+
+    /// Arguments de débit et de GOP remplaçant les réglages par défaut de l'encodeur
+    pub(crate) fn video_args(&self, encoder: &str, fps: i32) -> Vec<String> {
+        let mut args = Vec::new();
+
+        match self.rate_control {
+            RateControl::Crf { value } => {
+                let q = Self::crf_for(encoder, value).to_string();
+                if encoder.ends_with("_nvenc") {
+                    args.extend(["-rc".to_string(), "vbr".to_string(), "-cq".to_string(), q, "-b:v".to_string(), "0".to_string()]);
+                } else if encoder.ends_with("_qsv") {
+                    args.extend(["-global_quality".to_string(), q]);
+                } else if encoder.ends_with("_amf") {
+                    args.extend([
+                        "-rc".to_string(), "cqp".to_string(),
+                        "-qp_i".to_string(), q.clone(),
+                        "-qp_p".to_string(), q.clone(),
+                        "-qp_b".to_string(), q,
+                    ]);
+                } else {
+                    args.extend(["-crf".to_string(), q]);
+                    // libvpx / libaom n'utilisent le CRF qu'en mode qualité constante (b:v 0)
+                    if encoder == "libvpx-vp9" || encoder == "libaom-av1" {
+                        args.extend(["-b:v".to_string(), "0".to_string()]);
+                    }
+                }
+            }
+            RateControl::Bitrate { kbps, max_kbps } => {
+                let max = max_kbps.unwrap_or(kbps);
+                if encoder.ends_with("_amf") {
+                    args.extend(["-rc".to_string(), "vbr_peak".to_string()]);
+                } else if encoder.ends_with("_nvenc") {
+                    args.extend(["-rc".to_string(), "vbr".to_string()]);
+                }
+                args.extend([
+                    "-b:v".to_string(), format!("{}k", kbps),
+                    "-maxrate".to_string(), format!("{}k", max),
+                    "-bufsize".to_string(), format!("{}k", max * 2),
+                ]);
+            }
+            // Résolu en débit par `for_duration` avant l'encodage
+            RateControl::TargetSize { .. } => {}
+        }
+
+        // libaom et libvpx n'ont pas de preset : la vitesse passe par -cpu-used
+        if let Some(speed) = self.speed_rank() {
+            let cpu_used = match encoder {
+                "libaom-av1" => Some(["8", "7", "6", "5", "4", "3"][speed]),
+                "libvpx-vp9" => Some(["5", "4", "3", "2", "1", "0"][speed]),
+                _ => None,
+            };
+            if let Some(cpu_used) = cpu_used {
+                args.extend(["-cpu-used".to_string(), cpu_used.to_string()]);
+            }
+        }
+
+        // NVENC gère le double passage en interne, dans un seul encodage
+        if self.two_pass && encoder.ends_with("_nvenc") {
+            args.extend(["-multipass".to_string(), "fullres".to_string()]);
+        }
+
+        if let Some(interval) = self.keyframe_interval_s {
+            let gop = (interval * fps as f64).round().max(1.0) as i64;
+            args.extend(["-g".to_string(), gop.to_string()]);
+        }
+
+        args
+    }
+
+    /// Rang du preset x264, du plus rapide (0) au plus lent (5)
+    fn speed_rank(&self) -> Option<usize> {
+        Some(match self.preset.as_deref()? {
+            "ultrafast" | "superfast" => 0,
+            "veryfast" | "faster" => 1,
+            "fast" => 2,
+            "medium" => 3,
+            "slow" => 4,
+            _ => 5,
+        })
+    }
+
+    /// Preset à utiliser pour l'encodeur, `None` si le profil ne le change pas
+    pub(crate) fn preset_for(&self, encoder: &str) -> Option<Option<String>> {
+        let preset = self.preset.as_deref()?;
+        if is_software_x26x(encoder) {
+            return Some(Some(preset.to_string()));
+        }
+
+        let speed = self.speed_rank()?;
+        match encoder {
+            "libsvtav1" => Some(Some(["12", "10", "8", "6", "4", "2"][speed].to_string())),
+            e if e.ends_with("_nvenc") => Some(Some(["p1", "p2", "p3", "p4", "p6", "p7"][speed].to_string())),
+            _ => None,
+        }
+    }
+
+    /// Vrai si le double passage doit être fait en lançant FFmpeg deux fois
+    pub(crate) fn needs_two_runs(&self, encoder: &str) -> bool {
+        self.two_pass
+            && matches!(self.rate_control, RateControl::Bitrate { .. })
+            && supports_two_runs(encoder)
+    }
+
+    /// Arguments d'une passe (1 ou 2) pour un encodage en deux lancements
+    pub(crate) fn pass_args(encoder: &str, pass: u32, log_prefix: &Path) -> Vec<String> {
+        let log_prefix = log_prefix.to_string_lossy().to_string();
+        if encoder == "libx265" {
+            // libx265 ignore -pass, les statistiques passent par x265-params
+            let stats = format!("{}.log", log_prefix).replace('\\', "/").replace(':', "\\:");
+            return vec!["-x265-params".to_string(), format!("pass={}:stats={}", pass, stats)];
+        }
+        vec![
+            "-pass".to_string(), pass.to_string(),
+            "-passlogfile".to_string(), log_prefix,
+        ]
+    }
+}
+
+#[tauri::command]
+pub fn list_encoding_presets() -> Vec<serde_json::Value> {
+    EncodingPreset::ALL
+        .iter()
+        .map(|preset| {
+            serde_json::json!({
+                "id": preset,
+                "label": preset.label(),
+                "profile": preset.profile(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target_size(max_mb: f64) -> EncodingProfile {
+        EncodingProfile {
+            rate_control: RateControl::TargetSize { max_mb },
+            ..EncodingPreset::Whatsapp.profile()
+        }
+    }
+
+    fn video_kbps(profile: &EncodingProfile) -> u32 {
+        match profile.rate_control {
+            RateControl::Bitrate { kbps, .. } => kbps,
+            ref other => panic!("débit attendu, obtenu {:?}", other),
+        }
+    }
+
+    #[test]
+    fn profiles_without_target_size_are_unchanged() {
+        let profile = EncodingPreset::Youtube1080p.profile().for_duration(60.0, true, false).unwrap();
+        assert!(matches!(profile.rate_control, RateControl::Crf { value: 18 }));
+        assert!(!profile.two_pass);
+    }
+
+    #[test]
+    fn target_size_leaves_room_for_audio() {
+        // 16 Mo sur 60 s : ~2147 kb/s au total, dont 96 kb/s d'audio
+        let with_audio = target_size(16.0).for_duration(60.0, true, false).unwrap();
+        let without_audio = target_size(16.0).for_duration(60.0, false, false).unwrap();
+        assert_eq!(video_kbps(&with_audio), 2051);
+        assert_eq!(video_kbps(&without_audio), 2147);
+        assert!(with_audio.two_pass);
+    }
+
+    #[test]
+    fn too_small_target_size_is_rejected() {
+        // 1 Mo sur 10 min ne laisse même pas l'audio
+        assert!(target_size(1.0).for_duration(600.0, true, false).is_err());
+        assert!(target_size(1.0).for_duration(600.0, false, false).is_err());
+    }
+
+    #[test]
+    fn audio_only_target_size_lowers_the_audio_bitrate() {
+        // 1 Mo sur 200 s : ~40 kb/s, sous les 96 kb/s du profil
+        let profile = target_size(1.0).for_duration(200.0, true, true).unwrap();
+        assert_eq!(profile.audio_bitrate_kbps, 40);
+        assert!(matches!(profile.rate_control, RateControl::TargetSize { .. }));
+
+        // Taille large : le débit du profil est gardé
+        let profile = target_size(16.0).for_duration(200.0, true, true).unwrap();
+        assert_eq!(profile.audio_bitrate_kbps, 96);
+
+        assert!(target_size(1.0).for_duration(600.0, true, true).is_err());
+    }
+
+    #[test]
+    fn only_software_encoders_run_two_passes() {
+        let profile = target_size(16.0).for_duration(60.0, true, false).unwrap();
+        for encoder in ["libx264", "libx265", "libvpx-vp9", "libaom-av1"] {
+            assert!(profile.needs_two_runs(encoder), "{}", encoder);
+        }
+        for encoder in ["h264_nvenc", "hevc_qsv", "h264_amf", "h264_vaapi", "h264_videotoolbox", "libsvtav1"] {
+            assert!(!profile.needs_two_runs(encoder), "{}", encoder);
+        }
+    }
+
+    #[test]
+    fn presets_map_to_cpu_used_for_aom_and_vpx() {
+        let profile = EncodingPreset::Youtube1080p.profile();
+        let cpu_used = |encoder: &str| {
+            let args = profile.video_args(encoder, 30);
+            args.iter().position(|a| a == "-cpu-used").map(|i| args[i + 1].clone())
+        };
+        assert_eq!(cpu_used("libaom-av1").as_deref(), Some("5"));
+        assert_eq!(cpu_used("libvpx-vp9").as_deref(), Some("2"));
+        assert_eq!(cpu_used("libx264"), None);
+        assert_eq!(profile.preset_for("libx264"), Some(Some("medium".to_string())));
+        assert_eq!(profile.preset_for("libaom-av1"), None);
+    }
+}
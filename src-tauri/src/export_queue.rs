@@ -0,0 +1,440 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use tauri::{Emitter, Manager};
+
+use crate::exporter::{self, ExportChunkSpec, ExportError};
+use crate::subtitles::SoftSubtitleOptions;
+
+// File d'attente des jobs d'export (traités un par un par le worker)
+static JOB_SENDER: OnceLock<Mutex<Sender<String>>> = OnceLock::new();
+
+// Jobs déjà en file ou en cours, pour éviter de les lancer deux fois
+static QUEUED_JOBS: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// État global d'un job. Seuls les jobs `Queued` sont relancés au démarrage.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum JobStatus {
+    #[default]
+    Queued,
+    Failed { error: String },
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ChunkStatus {
+    Pending,
+    Running,
+    Done,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExportJobChunk {
+    pub spec: ExportChunkSpec,
+    pub status: ChunkStatus,
+    pub output: Option<String>,
+}
+
+/// Job d'export persisté dans `<app_data>/export-jobs/<export_id>.json`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExportJob {
+    pub export_id: String,
+    pub final_file_path: String,
+    #[serde(default)]
+    pub status: JobStatus,
+    pub workers: Option<usize>,
+    #[serde(default)]
+    pub soft_subtitles: Option<SoftSubtitleOptions>,
+    #[serde(default)]
+    pub start_time: Option<i32>,
+    pub chunks: Vec<ExportJobChunk>,
+}
+
+fn jobs_dir(app: &tauri::AppHandle) -> Result<PathBuf, ExportError> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ExportError::io(format!("Impossible de trouver le dossier app data: {}", e)))?
+        .join("export-jobs");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn job_path(app: &tauri::AppHandle, export_id: &str) -> Result<PathBuf, ExportError> {
+    // L'identifiant sert de nom de fichier : pas de séparateur ni de `..`
+    let valid = !export_id.is_empty()
+        && export_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(ExportError::invalid_input(format!("Identifiant d'export invalide: {:?}", export_id)));
+    }
+    Ok(jobs_dir(app)?.join(format!("{}.json", export_id)))
+}
+
+fn save_job(app: &tauri::AppHandle, job: &ExportJob) -> Result<(), ExportError> {
+    let path = job_path(app, &job.export_id)?;
+    let content = serde_json::to_string_pretty(job)
+        .map_err(|e| ExportError::io(format!("Erreur sérialisation job: {}", e)))?;
+
+    // Écriture atomique pour ne pas corrompre le job si l'app se ferme pendant l'écriture
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+fn load_job(path: &Path) -> Result<ExportJob, ExportError> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content)
+        .map_err(|e| ExportError::io(format!("Job d'export invalide {:?}: {}", path, e)))
+}
+
+fn load_jobs(app: &tauri::AppHandle) -> Result<Vec<ExportJob>, ExportError> {
+    let mut jobs = Vec::new();
+    for entry in fs::read_dir(jobs_dir(app)?)?.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match load_job(&path) {
+            Ok(job) => jobs.push(job),
+            Err(e) => println!("[export_queue] Job ignoré: {}", e),
+        }
+    }
+    jobs.sort_by(|a, b| a.export_id.cmp(&b.export_id));
+    Ok(jobs)
+}
+
+fn push_job(export_id: &str) -> Result<(), ExportError> {
+    {
+        let mut queued = QUEUED_JOBS.lock().map_err(|_| ExportError::io("Failed to lock export queue"))?;
+        if !queued.insert(export_id.to_string()) {
+            println!("[export_queue] Job {} déjà en file d'attente", export_id);
+            return Ok(());
+        }
+    }
+
+    let sender = JOB_SENDER
+        .get()
+        .ok_or_else(|| ExportError::io("Export queue not initialized"))?
+        .lock()
+        .map_err(|_| ExportError::io("Failed to lock export queue"))?;
+    sender
+        .send(export_id.to_string())
+        .map_err(|_| ExportError::io("Export queue worker stopped"))
+}
+
+// Exécute tous les chunks non terminés d'un job puis concatène les vidéos
+fn run_job(app: &tauri::AppHandle, export_id: &str) -> Result<(), ExportError> {
+    let path = job_path(app, export_id)?;
+    let mut job = load_job(&path)?;
+
+    println!("[export_queue] Reprise du job {} ({} chunk(s))", export_id, job.chunks.len());
+
+    // Un chunk en échec n'est relancé que par `resume_export_job`
+    if let Some(error) = job.chunks.iter().find_map(|c| match &c.status {
+        ChunkStatus::Failed { error } => Some(error.clone()),
+        _ => None,
+    }) {
+        return Err(ExportError::invalid_input(format!(
+            "Un chunk du job {} est en échec ({}), il doit être relancé explicitement",
+            export_id, error
+        )));
+    }
+
+    // Mesure du volume commune à tous les chunks, enregistrée pour les reprises
+    let mut specs: Vec<ExportChunkSpec> = job.chunks.iter().map(|c| c.spec.clone()).collect();
+    if exporter::measure_shared_loudness(export_id, &mut specs, app)? {
+        for (chunk, spec) in job.chunks.iter_mut().zip(specs) {
+            chunk.spec = spec;
+        }
+        save_job(app, &job)?;
+    }
+
+    // Un chunk terminé n'est ignoré que si son fichier de sortie existe toujours
+    let remaining: Vec<usize> = (0..job.chunks.len())
+        .filter(|&i| {
+            let chunk = &job.chunks[i];
+            let done = chunk.status == ChunkStatus::Done
+                && chunk.output.as_deref().map(|o| Path::new(o).exists()).unwrap_or(false);
+            if done {
+                println!("[export_queue] Chunk {:?} déjà terminé, ignoré", chunk.spec.chunk_index);
+            }
+            !done
+        })
+        .collect();
+
+    if !remaining.is_empty() {
+        for &i in &remaining {
+            job.chunks[i].status = ChunkStatus::Running;
+        }
+        save_job(app, &job)?;
+
+        let specs: Vec<ExportChunkSpec> = remaining.iter().map(|&i| job.chunks[i].spec.clone()).collect();
+        let workers = job.workers;
+        let shared_job = Mutex::new(job);
+
+        // Chaque chunk terminé est enregistré immédiatement pour pouvoir reprendre plus tard
+        let result = exporter::export_chunks_parallel(export_id, specs, workers, app, &|pos, result| {
+            let Ok(mut job) = shared_job.lock() else {
+                return;
+            };
+            let chunk = &mut job.chunks[remaining[pos]];
+            match result {
+                Ok(output) => {
+                    chunk.status = ChunkStatus::Done;
+                    chunk.output = Some(output.clone());
+                }
+                Err(e) => chunk.status = ChunkStatus::Failed { error: e.to_string() },
+            }
+            if let Err(e) = save_job(app, &job) {
+                println!("[export_queue] Impossible d'enregistrer le job {}: {}", export_id, e);
+            }
+        });
+
+        job = shared_job.into_inner().map_err(|_| ExportError::io("Failed to lock export job"))?;
+
+        // Les chunks jamais lancés (annulation ou erreur) repassent en attente
+        for chunk in job.chunks.iter_mut() {
+            if chunk.status == ChunkStatus::Running {
+                chunk.status = ChunkStatus::Pending;
+            }
+        }
+        save_job(app, &job)?;
+        result?;
+    }
+
+    let outputs: Vec<String> = job.chunks.iter().filter_map(|c| c.output.clone()).collect();
+    exporter::emit_export_phase(app, export_id, None, exporter::ExportPhase::Concatenating);
+    exporter::concat_files(
+        outputs,
+        job.final_file_path.clone(),
+        job.soft_subtitles.clone(),
+        job.start_time,
+        export_id,
+        app,
+    )?;
+
+    remove_job_files(&job);
+    let _ = fs::remove_file(&path);
+
+    let output_file_name = Path::new(&job.final_file_path)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let file_size = fs::metadata(&job.final_file_path).map(|m| m.len()).ok();
+    let mut completion_data = serde_json::json!({
+        "filename": output_file_name,
+        "exportId": job.export_id,
+        "fullPath": job.final_file_path,
+        "fileSize": file_size
+    });
+
+    // La taille cible a été répartie entre les chunks : on compare la vidéo finale au total
+    let target_mb: Option<f64> = job
+        .chunks
+        .iter()
+        .map(|c| c.spec.encoding_profile.as_ref().and_then(|p| p.resolve().target_size_mb()))
+        .sum();
+    if let (Some(size), Some(target_mb)) = (file_size, target_mb) {
+        println!("[export_queue] Taille obtenue: {:.2} Mo (cible {:.2} Mo)", size as f64 / (1024.0 * 1024.0), target_mb);
+        completion_data["targetSizeMb"] = serde_json::json!(target_mb);
+    }
+    let loudness = job.chunks.first().and_then(|c| c.spec.output_format.as_ref()).and_then(|f| f.loudness.as_ref());
+    if let Some((options, measured)) = loudness.and_then(|o| o.measured.map(|m| (o, m))) {
+        completion_data["loudness"] = options.report(&measured);
+    }
+    let _ = app.emit("export-complete", completion_data);
+
+    println!("[export_queue] ✅ Job {} terminé: {}", export_id, job.final_file_path);
+    Ok(())
+}
+
+// Dossier de travail de l'export (`exports/<export_id>`), qui contient les images des chunks
+fn job_dir(job: &ExportJob) -> Option<PathBuf> {
+    let first = job.chunks.first()?;
+    Path::new(&first.spec.imgs_folder)
+        .parent()
+        .filter(|dir| dir.file_name().and_then(|n| n.to_str()) == Some(job.export_id.as_str()))
+        .map(Path::to_path_buf)
+}
+
+// Supprime les vidéos de chunks, leurs images et le dossier de travail de l'export.
+// Appelé seulement quand le job ne peut plus être repris : terminé, annulé ou abandonné.
+fn remove_job_files(job: &ExportJob) {
+    for chunk in &job.chunks {
+        if let Some(output) = &chunk.output {
+            if output != &job.final_file_path {
+                let _ = fs::remove_file(output);
+            }
+        }
+        let _ = fs::remove_dir_all(&chunk.spec.imgs_folder);
+    }
+    if let Some(dir) = job_dir(job) {
+        if !Path::new(&job.final_file_path).starts_with(&dir) {
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+}
+
+// Enregistre l'état final d'un job qui n'a pas abouti. Un job annulé est supprimé avec ses fichiers.
+fn close_job(app: &tauri::AppHandle, export_id: &str, status: JobStatus) -> Result<(), ExportError> {
+    let path = job_path(app, export_id)?;
+    let mut job = load_job(&path)?;
+    job.status = status;
+    save_job(app, &job)?;
+
+    if job.status == JobStatus::Cancelled {
+        remove_job_files(&job);
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Démarre le worker de la file d'export et relance les jobs interrompus
+pub fn init(app: tauri::AppHandle) {
+    let (tx, rx) = mpsc::channel::<String>();
+    if JOB_SENDER.set(Mutex::new(tx)).is_err() {
+        return;
+    }
+
+    let worker_app = app.clone();
+    std::thread::spawn(move || {
+        for export_id in rx {
+            let result = exporter::run_cancellable(&export_id, &worker_app, || {
+                let result = run_job(&worker_app, &export_id);
+                // Un ffmpeg tué par l'annulation remonte comme une erreur d'encodage
+                if exporter::export_token(&export_id).is_cancelled() {
+                    return Err(ExportError::Cancelled { export_id: export_id.clone() });
+                }
+                result
+            });
+
+            let status = match result {
+                Ok(()) => None,
+                Err(ExportError::Cancelled { .. }) => {
+                    println!("[export_queue] Job {} annulé", export_id);
+                    Some(JobStatus::Cancelled)
+                }
+                Err(e) => {
+                    println!("[export_queue] Job {} en échec: {}", export_id, e);
+                    exporter::emit_export_error(&worker_app, &export_id, None, &e);
+                    Some(JobStatus::Failed { error: e.to_string() })
+                }
+            };
+            if let Some(status) = status {
+                if let Err(e) = close_job(&worker_app, &export_id, status) {
+                    println!("[export_queue] Impossible de mettre à jour le job {}: {}", export_id, e);
+                }
+            }
+            if let Ok(mut queued) = QUEUED_JOBS.lock() {
+                queued.remove(&export_id);
+            }
+        }
+    });
+
+    // Les chunks restés "running" ont été interrompus par la fermeture de l'app.
+    // Un job en échec ou annulé attend une reprise explicite.
+    match load_jobs(&app) {
+        Ok(jobs) => {
+            for mut job in jobs {
+                let failed = job.chunks.iter().any(|c| matches!(c.status, ChunkStatus::Failed { .. }));
+                if job.status != JobStatus::Queued || failed {
+                    println!("[export_queue] Job {} non relancé ({:?})", job.export_id, job.status);
+                    continue;
+                }
+                for chunk in job.chunks.iter_mut() {
+                    if chunk.status == ChunkStatus::Running {
+                        chunk.status = ChunkStatus::Pending;
+                    }
+                }
+                if let Err(e) = save_job(&app, &job) {
+                    println!("[export_queue] Impossible de mettre à jour le job {}: {}", job.export_id, e);
+                    continue;
+                }
+                println!("[export_queue] Job interrompu trouvé: {}", job.export_id);
+                if let Err(e) = push_job(&job.export_id) {
+                    println!("[export_queue] Impossible de relancer le job {}: {}", job.export_id, e);
+                }
+            }
+        }
+        Err(e) => println!("[export_queue] Lecture des jobs impossible: {}", e),
+    }
+}
+
+#[tauri::command]
+pub fn enqueue_export_job(
+    export_id: String,
+    final_file_path: String,
+    chunks: Vec<ExportChunkSpec>,
+    workers: Option<usize>,
+    soft_subtitles: Option<SoftSubtitleOptions>,
+    app: tauri::AppHandle,
+) -> Result<(), ExportError> {
+    if chunks.is_empty() {
+        return Err(ExportError::invalid_input("Aucun chunk fourni pour le job d'export"));
+    }
+
+    let mut chunks = chunks;
+    exporter::split_target_size(&mut chunks);
+    exporter::pin_loop_layer_ends(&mut chunks);
+
+    let job = ExportJob {
+        export_id: export_id.clone(),
+        final_file_path,
+        status: JobStatus::Queued,
+        workers,
+        soft_subtitles,
+        start_time: chunks.iter().map(|c| c.start_time).min(),
+        chunks: chunks
+            .into_iter()
+            .map(|spec| ExportJobChunk { spec, status: ChunkStatus::Pending, output: None })
+            .collect(),
+    };
+    save_job(&app, &job)?;
+
+    println!("[export_queue] Job {} ajouté ({} chunk(s))", export_id, job.chunks.len());
+    push_job(&export_id)
+}
+
+#[tauri::command]
+pub fn list_export_jobs(app: tauri::AppHandle) -> Result<Vec<ExportJob>, ExportError> {
+    load_jobs(&app)
+}
+
+#[tauri::command]
+pub fn resume_export_job(export_id: String, app: tauri::AppHandle) -> Result<(), ExportError> {
+    let mut job = load_job(&job_path(&app, &export_id)?)?;
+
+    // Les chunks en échec sont remis en attente pour être relancés
+    for chunk in job.chunks.iter_mut() {
+        if matches!(chunk.status, ChunkStatus::Failed { .. }) {
+            chunk.status = ChunkStatus::Pending;
+        }
+    }
+    job.status = JobStatus::Queued;
+    save_job(&app, &job)?;
+
+    push_job(&export_id)
+}
+
+#[tauri::command]
+pub fn discard_export_job(export_id: String, app: tauri::AppHandle) -> Result<(), ExportError> {
+    let path = job_path(&app, &export_id)?;
+    if QUEUED_JOBS.lock().map(|q| q.contains(&export_id)).unwrap_or(false) {
+        return Err(ExportError::invalid_input(format!("Le job {} est en cours, il faut d'abord l'annuler", export_id)));
+    }
+    if path.exists() {
+        if let Ok(job) = load_job(&path) {
+            remove_job_files(&job);
+        }
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
@@ -1,21 +1,332 @@
 use std::collections::HashMap;
 use std::fs;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, LazyLock, Mutex};
 use std::time::Instant;
 use tauri::Emitter;
 use tokio::task;
 
+use crate::audio::{self, AudioLayer, AudioSource};
+use crate::background::{BackgroundClip, BackgroundSource, FitMode, PreparedBackground};
+use crate::encoder_backend;
+use crate::encoders;
+use crate::encoding_profile::{EncodingProfile, EncodingProfileChoice, RateControl};
+use crate::loudness::{self, LoudnessMeasurement, LoudnessOptions};
+use crate::preproc_cache;
+use crate::subtitles::{self, SoftSubtitleOptions};
+
 // Expose la dernière durée d'export terminée (en secondes)
 static LAST_EXPORT_TIME_S: Mutex<Option<f64>> = Mutex::new(None);
 
-// Gestionnaire des processus actifs pour pouvoir les annuler
-static ACTIVE_EXPORTS: LazyLock<Mutex<HashMap<String, Arc<Mutex<Option<std::process::Child>>>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+type SharedChild = Arc<Mutex<Option<std::process::Child>>>;
+
+// Jetons des exports en cours, pour pouvoir les annuler
+static ACTIVE_EXPORTS: LazyLock<Mutex<HashMap<String, Arc<CancellationToken>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Jeton d'annulation d'un export.
+///
+/// Tous les ffmpeg lancés pour l'export (prétraitement, chunks, concaténation) s'y enregistrent,
+/// ainsi que les fichiers à supprimer à la fin de l'export.
+#[derive(Default)]
+pub(crate) struct CancellationToken {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+    children: Mutex<Vec<SharedChild>>,
+    /// Fichiers de travail (ffconcat, ffgraph, ...), supprimés dans tous les cas
+    temp_files: Mutex<Vec<PathBuf>>,
+    /// Sorties en cours d'écriture, supprimées si l'export est annulé ou échoue
+    partial_outputs: Mutex<Vec<PathBuf>>,
+    /// Durées des sources déjà sondées, partagées par tous les chunks de l'export
+    source_durations: Mutex<HashMap<String, f64>>,
+}
+
+impl CancellationToken {
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Marque l'export comme annulé et tue ses processus. Renvoie le nombre de processus arrêtés.
+    fn cancel(&self) -> usize {
+        self.cancelled.store(true, Ordering::SeqCst);
+
+        let Ok(children) = self.children.lock() else {
+            return 0;
+        };
+        let mut killed = 0;
+        for process_ref in children.iter() {
+            let Ok(mut process_guard) = process_ref.lock() else {
+                continue;
+            };
+            if let Some(mut child) = process_guard.take() {
+                match child.kill() {
+                    Ok(_) => {
+                        let _ = child.wait(); // Nettoyer le processus zombie
+                        killed += 1;
+                    }
+                    Err(e) => println!("[cancel_export] Erreur lors de l'arrêt du processus: {:?}", e),
+                }
+            }
+        }
+        killed
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Suspend ou relance tous les processus de l'export. Renvoie le nombre de processus concernés.
+    fn set_paused(&self, paused: bool) -> usize {
+        self.paused.store(paused, Ordering::SeqCst);
+
+        let Ok(children) = self.children.lock() else {
+            return 0;
+        };
+        let mut count = 0;
+        for process_ref in children.iter() {
+            let Ok(process_guard) = process_ref.lock() else {
+                continue;
+            };
+            if let Some(child) = process_guard.as_ref() {
+                match set_process_suspended(child, paused) {
+                    Ok(_) => count += 1,
+                    Err(e) => println!("[pause_export] Impossible de suspendre/relancer le processus {}: {}", child.id(), e),
+                }
+            }
+        }
+        count
+    }
+
+    /// Enregistre un processus. S'il est lancé après l'annulation, il est tué immédiatement ;
+    /// pendant une pause, il est suspendu jusqu'à la reprise.
+    fn register(&self, process_ref: &SharedChild) {
+        let Ok(mut children) = self.children.lock() else {
+            return;
+        };
+        children.push(process_ref.clone());
+        if self.is_cancelled() {
+            if let Some(mut child) = process_ref.lock().ok().and_then(|mut c| c.take()) {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        } else if self.is_paused() {
+            if let Some(child) = process_ref.lock().ok().as_ref().and_then(|c| c.as_ref()) {
+                let _ = set_process_suspended(child, true);
+            }
+        }
+    }
+
+    fn unregister(&self, process_ref: &SharedChild) {
+        if let Ok(mut children) = self.children.lock() {
+            children.retain(|c| !Arc::ptr_eq(c, process_ref));
+        }
+    }
+
+    pub(crate) fn track_temp_file(&self, path: impl Into<PathBuf>) {
+        if let Ok(mut files) = self.temp_files.lock() {
+            files.push(path.into());
+        }
+    }
+
+    pub(crate) fn track_output(&self, path: impl Into<PathBuf>) {
+        if let Ok(mut outputs) = self.partial_outputs.lock() {
+            outputs.push(path.into());
+        }
+    }
+
+    /// Durée d'un fichier source (s), sondée par ffprobe une seule fois pour tout l'export
+    pub(crate) fn source_duration_s(&self, path: &str) -> f64 {
+        if let Some(duration) = self.source_durations.lock().ok().and_then(|d| d.get(path).copied()) {
+            return duration;
+        }
+        let duration = ffprobe_duration_sec(path);
+        if let Ok(mut durations) = self.source_durations.lock() {
+            durations.insert(path.to_string(), duration);
+        }
+        duration
+    }
+
+    /// La sortie est complète : elle ne sera plus supprimée
+    pub(crate) fn output_done(&self, path: impl AsRef<Path>) {
+        if let Ok(mut outputs) = self.partial_outputs.lock() {
+            outputs.retain(|p| p != path.as_ref());
+        }
+    }
+
+    /// Supprime les fichiers de travail, et les sorties partielles si `failed`. Renvoie les chemins supprimés.
+    fn cleanup(&self, failed: bool) -> Vec<String> {
+        let mut paths = self.temp_files.lock().map(|mut f| std::mem::take(&mut *f)).unwrap_or_default();
+        let outputs = self.partial_outputs.lock().map(|mut o| std::mem::take(&mut *o)).unwrap_or_default();
+        if failed {
+            paths.extend(outputs);
+        }
+
+        let mut removed = Vec::new();
+        for path in paths {
+            let result = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+            if result.is_ok() {
+                removed.push(path.to_string_lossy().to_string());
+            }
+        }
+        removed
+    }
+}
+
+/// Suspend un processus avec SIGSTOP, ou le relance avec SIGCONT
+#[cfg(unix)]
+fn set_process_suspended(child: &std::process::Child, suspended: bool) -> std::io::Result<()> {
+    let signal = if suspended { libc::SIGSTOP } else { libc::SIGCONT };
+    if unsafe { libc::kill(child.id() as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Suspend ou relance tous les threads d'un processus (NtSuspendProcess / NtResumeProcess)
+#[cfg(windows)]
+fn set_process_suspended(child: &std::process::Child, suspended: bool) -> std::io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+
+    #[link(name = "ntdll")]
+    extern "system" {
+        fn NtSuspendProcess(handle: std::os::windows::io::RawHandle) -> i32;
+        fn NtResumeProcess(handle: std::os::windows::io::RawHandle) -> i32;
+    }
+
+    let handle = child.as_raw_handle();
+    let status = unsafe {
+        if suspended { NtSuspendProcess(handle) } else { NtResumeProcess(handle) }
+    };
+    if status >= 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("NTSTATUS {:#x}", status)))
+    }
+}
+
+/// Vrai si l'export est en pause (sans créer de jeton)
+fn is_export_paused(export_id: &str) -> bool {
+    ACTIVE_EXPORTS
+        .lock()
+        .ok()
+        .and_then(|active_exports| active_exports.get(export_id).map(|t| t.is_paused()))
+        .unwrap_or(false)
+}
+
+/// Jeton de l'export, créé s'il n'existe pas encore
+pub(crate) fn export_token(export_id: &str) -> Arc<CancellationToken> {
+    let mut active_exports = ACTIVE_EXPORTS.lock().unwrap_or_else(|e| e.into_inner());
+    active_exports.entry(export_id.to_string()).or_default().clone()
+}
+
+/// Exécute un export de premier niveau (commande ou job de la file) avec son jeton d'annulation.
+///
+/// À la fin, les fichiers de travail sont supprimés. Si l'export est annulé ou échoue, les sorties
+/// partielles le sont aussi et la liste des fichiers supprimés est envoyée dans `export-cleanup`.
+/// Les vidéos du cache de prétraitement réservées par l'export redeviennent évinçables.
+pub(crate) fn run_cancellable<T>(
+    export_id: &str,
+    app_handle: &tauri::AppHandle,
+    run: impl FnOnce() -> Result<T, ExportError>,
+) -> Result<T, ExportError> {
+    let token = export_token(export_id);
+    let result = run();
+
+    if let Ok(mut active_exports) = ACTIVE_EXPORTS.lock() {
+        active_exports.remove(export_id);
+    }
+    preproc_cache::release(export_id);
+
+    let removed = token.cleanup(result.is_err());
+    for path in &removed {
+        println!("[cleanup] Supprimé: {}", path);
+    }
+    if result.is_err() {
+        let _ = app_handle.emit("export-cleanup", serde_json::json!({
+            "export_id": export_id,
+            "cancelled": token.is_cancelled(),
+            "removed_files": removed
+        }));
+    }
+
+    result
+}
+
+/// Erreurs d'export renvoyées au frontend.
+///
+/// Sérialisée avec un champ `kind` (ex: `{"kind": "encoder_failed", "exit_code": 1, ...}`)
+/// pour que l'UI puisse réagir à chaque cas sans parser le message.
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportError {
+    FfmpegNotFound,
+    NoFramesFound { folder: String },
+    FirstFrameNotZero { first_frame: String },
+    Cancelled { export_id: String },
+    ExportNotFound { export_id: String },
+    EncoderFailed { exit_code: Option<i32>, log_path: String, stderr: String },
+    PreprocessFailed { source_path: String },
+    ConcatFailed { exit_code: Option<i32>, stderr: String },
+    InvalidInput { message: String },
+    Io { message: String },
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::FfmpegNotFound => write!(f, "Exécutable FFmpeg introuvable"),
+            ExportError::NoFramesFound { folder } => write!(f, "Aucune image .png trouvée dans {}", folder),
+            ExportError::FirstFrameNotZero { first_frame } => write!(f, "La première image doit être '0.png' (timestamp 0 ms), trouvé '{}'.", first_frame),
+            ExportError::Cancelled { export_id } => write!(f, "Export {} annulé", export_id),
+            ExportError::ExportNotFound { export_id } => write!(f, "Export {} non trouvé ou déjà terminé", export_id),
+            ExportError::EncoderFailed { exit_code, log_path, stderr } => write!(f, "FFmpeg a échoué pendant l'export vidéo (code: {:?})\n\nVoir le fichier de log: {}\n\nSortie d'erreur:\n{}", exit_code, log_path, stderr),
+            ExportError::PreprocessFailed { source_path } => write!(f, "FFmpeg a échoué lors du prétraitement de {}", source_path),
+            ExportError::ConcatFailed { exit_code, stderr } => write!(f, "FFmpeg a échoué lors de la concaténation (code: {:?})\nSTDERR: {}", exit_code, stderr),
+            ExportError::InvalidInput { message } => write!(f, "{}", message),
+            ExportError::Io { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::Io { message: e.to_string() }
+    }
+}
+
+impl ExportError {
+    pub(crate) fn invalid_input(message: impl Into<String>) -> Self {
+        ExportError::InvalidInput { message: message.into() }
+    }
+
+    pub(crate) fn io(message: impl Into<String>) -> Self {
+        ExportError::Io { message: message.into() }
+    }
+}
+
+// Émet un événement `export-error` avec le message lisible et l'erreur typée
+pub(crate) fn emit_export_error(app_handle: &tauri::AppHandle, export_id: &str, chunk_index: Option<i32>, err: &ExportError) {
+    let mut error_data = serde_json::json!({
+        "export_id": export_id,
+        "error": err.to_string(),
+        "details": err
+    });
+
+    // Ajouter chunk_index si fourni
+    if let Some(chunk_idx) = chunk_index {
+        error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+    }
+
+    let _ = app_handle.emit("export-error", error_data);
+}
 
 // Fonction utilitaire pour configurer les commandes et cacher les fenêtres CMD sur Windows
-fn configure_command_no_window(cmd: &mut Command) {
+pub(crate) fn configure_command_no_window(cmd: &mut Command) {
     #[cfg(target_os = "windows")]
     {
         use std::os::windows::process::CommandExt;
@@ -88,6 +399,10 @@ fn resolve_ffmpeg_binary() -> Option<String> {
     None
 }
 
+pub(crate) fn ffmpeg_binary() -> Result<String, ExportError> {
+    resolve_ffmpeg_binary().ok_or(ExportError::FfmpegNotFound)
+}
+
 fn resolve_ffprobe_binary() -> String {
     // Essayer d'abord le chemin relatif standard
     let ffprobe_path = if cfg!(target_os = "windows") {
@@ -152,293 +467,386 @@ fn resolve_ffprobe_binary() -> String {
     "ffprobe".to_string()
 }
 
-/// Teste si NVENC est réellement disponible en essayant un encodage rapide
-fn test_nvenc_availability(ffmpeg_path: Option<&str>) -> bool {
-    let exe = ffmpeg_path.unwrap_or("ffmpeg");
-    
-    println!("[nvenc_test] Test de disponibilité NVENC...");
-    
-    // Créer une entrée vidéo de test très courte (1 frame noir)
-    // NVENC nécessite une résolution minimale (généralement 128x128 ou plus)
-    let mut cmd = Command::new(exe);
-    cmd.args(&[
-        "-y",
-        "-hide_banner",
-        "-loglevel", "error",
-        "-f", "lavfi",
-        "-i", "color=c=black:s=128x128:r=1:d=0.04", // Résolution minimum NVENC, très courte
-        "-c:v", "h264_nvenc",
-        "-preset", "fast",
-        "-pix_fmt", "yuv420p",
-        "-frames:v", "1",
-        "-f", "null", // Sortie nulle pour éviter d'écrire un fichier
-        "-"
-    ]);
+/// Famille de codec vidéo demandée pour l'export
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    #[default]
+    H264,
+    Hevc,
+    Av1,
+    Vp9,
+}
+
+impl VideoCodec {
+    pub(crate) const ALL: [VideoCodec; 4] = [VideoCodec::H264, VideoCodec::Hevc, VideoCodec::Av1, VideoCodec::Vp9];
     
-    configure_command_no_window(&mut cmd);
+    /// Encodeurs matériels, par ordre de préférence
+    pub(crate) fn hw_encoders(self) -> Vec<&'static str> {
+        encoder_backend::hw_encoders(self)
+    }
     
-    match cmd.output() {
-        Ok(output) => {
-            let success = output.status.success();
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            
-            if success {
-                println!("[nvenc_test] ✓ NVENC disponible et fonctionnel");
-                true
-            } else {
-                // Analyser les erreurs pour distinguer "pas disponible" vs "erreur de config"
-                let stderr_lower = stderr.to_lowercase();
-                
-                if stderr_lower.contains("cannot load nvcuda.dll") || 
-                   stderr_lower.contains("no nvidia devices") ||
-                   stderr_lower.contains("cuda") ||
-                   stderr_lower.contains("driver") {
-                    println!("[nvenc_test] ✗ NVENC non disponible (pas de GPU NVIDIA ou drivers manquants)");
-                    false
-                } else if stderr_lower.contains("frame dimension") {
-                    // Si c'est juste un problème de dimensions, essayer avec une plus grande résolution
-                    println!("[nvenc_test] Retry avec résolution plus grande...");
-                    test_nvenc_with_larger_resolution(ffmpeg_path)
-                } else {
-                    println!("[nvenc_test] ✗ NVENC erreur: {}", stderr.trim());
-                    false
-                }
-            }
+    /// Encodeurs logiciels, par ordre de préférence
+    pub(crate) fn software_encoders(self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::H264 => &["libx264"],
+            VideoCodec::Hevc => &["libx265"],
+            VideoCodec::Av1 => &["libsvtav1", "libaom-av1"],
+            VideoCodec::Vp9 => &["libvpx-vp9"],
         }
-        Err(e) => {
-            println!("[nvenc_test] ✗ Erreur lors du test NVENC: {}", e);
-            false
+    }
+}
+
+/// Conteneur de sortie
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputContainer {
+    Mp4,
+    Mkv,
+    Webm,
+    Mov,
+}
+
+impl OutputContainer {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputContainer::Mp4 => "mp4",
+            OutputContainer::Mkv => "mkv",
+            OutputContainer::Webm => "webm",
+            OutputContainer::Mov => "mov",
+        }
+    }
+
+    fn supports(self, codec: VideoCodec) -> bool {
+        match self {
+            OutputContainer::Mkv => true,
+            OutputContainer::Webm => matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1),
+            OutputContainer::Mp4 => true,
+            OutputContainer::Mov => matches!(codec, VideoCodec::H264 | VideoCodec::Hevc),
         }
     }
 }
 
-fn test_nvenc_with_larger_resolution(ffmpeg_path: Option<&str>) -> bool {
-    let exe = ffmpeg_path.unwrap_or("ffmpeg");
-    
-    let mut cmd = Command::new(exe);
-    cmd.args(&[
-        "-y",
-        "-hide_banner",
-        "-loglevel", "error",
-        "-f", "lavfi",
-        "-i", "color=c=black:s=256x256:r=1:d=0.04", // Résolution encore plus grande
-        "-c:v", "h264_nvenc",
-        "-preset", "fast",
-        "-pix_fmt", "yuv420p",
-        "-frames:v", "1",
-        "-f", "null",
-        "-"
-    ]);
-    
-    configure_command_no_window(&mut cmd);
-    
-    match cmd.output() {
-        Ok(output) => {
-            let success = output.status.success();
-            if success {
-                println!("[nvenc_test] ✓ NVENC disponible avec résolution 256x256");
-            } else {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                println!("[nvenc_test] ✗ NVENC toujours non disponible: {}", stderr.trim());
-            }
-            success
+/// Codec et conteneur de sortie. Sans conteneur, celui-ci est déduit de l'extension du fichier.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct OutputFormat {
+    #[serde(default)]
+    pub codec: VideoCodec,
+    #[serde(default)]
+    pub container: Option<OutputContainer>,
+    /// N'exporte que la piste audio (la vidéo n'est pas encodée)
+    #[serde(default)]
+    pub audio_only: bool,
+    /// Normalise le volume de l'audio exporté
+    #[serde(default)]
+    pub loudness: Option<LoudnessOptions>,
+    /// Périphérique de l'encodeur matériel (ex: /dev/dri/renderD129 pour VAAPI), sinon tous sont essayés
+    #[serde(default)]
+    pub hw_device: Option<String>,
+}
+
+impl OutputFormat {
+    /// Chemin de sortie avec l'extension du conteneur choisi
+    fn output_path(&self, final_file_path: &str) -> String {
+        match self.container {
+            Some(container) => Path::new(final_file_path)
+                .with_extension(container.extension())
+                .to_string_lossy()
+                .to_string(),
+            None => final_file_path.to_string(),
         }
-        Err(e) => {
-            println!("[nvenc_test] ✗ Erreur test résolution plus grande: {}", e);
-            false
+    }
+
+    fn validate(&self) -> Result<(), ExportError> {
+        match self.container {
+            Some(container) if !self.audio_only && !container.supports(self.codec) => Err(ExportError::invalid_input(format!(
+                "Le codec {:?} n'est pas supporté dans un conteneur {}",
+                self.codec,
+                container.extension()
+            ))),
+            _ => Ok(()),
         }
     }
 }
 
-fn probe_hw_encoders(ffmpeg_path: Option<&str>) -> Vec<String> {
-    let exe = ffmpeg_path.unwrap_or("ffmpeg");
-    
-    let output = match Command::new(exe)
-        .args(&["-hide_banner", "-encoders"])
-        .output()
-    {
-        Ok(output) => output,
-        Err(_) => return Vec::new(),
-    };
-    
-    let txt = String::from_utf8_lossy(&output.stdout).to_lowercase();
-    let mut found = Vec::new();
-    
-    if txt.contains("h264_nvenc") {
-        found.push("h264_nvenc".to_string());
+/// Codec audio adapté au conteneur (le WebM n'accepte pas l'AAC)
+fn audio_codec_for_extension(ext: &str) -> &'static str {
+    match ext {
+        "webm" | "ogg" | "opus" => "libopus",
+        "mp3" => "libmp3lame",
+        _ => "aac",
     }
-    if txt.contains("h264_qsv") {
-        found.push("h264_qsv".to_string());
+}
+
+// Encodeur H.264 des prétraitements, sur le même périphérique que l'encodage final
+fn choose_best_codec(prefer_hw: bool, hw_device: Option<&str>) -> (String, Vec<String>, HashMap<String, Option<String>>) {
+    choose_codec(VideoCodec::H264, prefer_hw, hw_device, false)
+}
+
+/// Choisit l'encodeur vidéo. Avec `two_pass` (taille cible), seul un encodeur logiciel capable
+/// d'un vrai double passage est utilisé : les encodeurs matériels n'encodent qu'en une passe.
+fn choose_codec(family: VideoCodec, prefer_hw: bool, hw_device: Option<&str>, two_pass: bool) -> (String, Vec<String>, HashMap<String, Option<String>>) {
+    if prefer_hw && two_pass {
+        println!("[codec] Taille cible: encodage logiciel en deux passes");
     }
-    if txt.contains("h264_amf") {
-        found.push("h264_amf".to_string());
+    if prefer_hw && !two_pass {
+        if let Some((encoder, (params, extra))) = encoder_backend::find_working_encoder(family, hw_device) {
+            println!("[codec] Utilisation de l'encodeur hardware: {}", encoder);
+            return (encoder.to_string(), params, extra);
+        }
     }
     
-    found
+    software_codec(family, two_pass)
 }
 
-fn choose_best_codec(prefer_hw: bool) -> (String, Vec<String>, HashMap<String, Option<String>>) {
-    let ffmpeg_exe = resolve_ffmpeg_binary();
-    let hw = if prefer_hw {
-        probe_hw_encoders(ffmpeg_exe.as_deref())
-    } else {
-        Vec::new()
-    };
+fn software_codec(family: VideoCodec, two_pass: bool) -> (String, Vec<String>, HashMap<String, Option<String>>) {
+    let to_strings = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+    let mut extra = HashMap::new();
     
-    if !hw.is_empty() {
-        // Tester spécifiquement NVENC s'il est détecté
-        if hw[0] == "h264_nvenc" {
-            if test_nvenc_availability(ffmpeg_exe.as_deref()) {
-                println!("[codec] Utilisation de NVENC (accélération GPU NVIDIA)");
-                let codec = hw[0].clone();
-                let params = vec!["-pix_fmt".to_string(), "yuv420p".to_string()];
-                let mut extra = HashMap::new();
-                extra.insert("preset".to_string(), Some("fast".to_string()));
-                return (codec, params, extra);
+    let (codec, params) = match family {
+        VideoCodec::H264 => {
+            extra.insert("preset".to_string(), Some("ultrafast".to_string()));
+            ("libx264", to_strings(&["-pix_fmt", "yuv420p", "-crf", "22", "-tune", "zerolatency", "-bf", "0"]))
+        }
+        VideoCodec::Hevc => {
+            extra.insert("preset".to_string(), Some("fast".to_string()));
+            ("libx265", to_strings(&["-pix_fmt", "yuv420p", "-crf", "26"]))
+        }
+        VideoCodec::Av1 => {
+            // SVT-AV1 est bien plus rapide que libaom, on le privilégie s'il est présent
+            // (sauf en deux passes, que seul libaom fait ici)
+            if !two_pass && encoders::is_encoder_available("libsvtav1") {
+                extra.insert("preset".to_string(), Some("8".to_string()));
+                ("libsvtav1", to_strings(&["-pix_fmt", "yuv420p", "-crf", "35"]))
             } else {
-                println!("[codec] NVENC détecté mais non fonctionnel, fallback vers libx264");
+                extra.insert("preset".to_string(), None);
+                ("libaom-av1", to_strings(&["-pix_fmt", "yuv420p", "-crf", "32", "-b:v", "0", "-cpu-used", "6", "-row-mt", "1"]))
             }
-        } else {
-            // Pour les autres encodeurs hardware (QSV, AMF), utiliser directement
-            println!("[codec] Utilisation de l'encodeur hardware: {}", hw[0]);
-            let codec = hw[0].clone();
-            let params = vec!["-pix_fmt".to_string(), "yuv420p".to_string()];
-            let mut extra = HashMap::new();
+        }
+        VideoCodec::Vp9 => {
             extra.insert("preset".to_string(), None);
-            return (codec, params, extra);
+            ("libvpx-vp9", to_strings(&["-pix_fmt", "yuv420p", "-crf", "32", "-b:v", "0", "-deadline", "good", "-cpu-used", "4", "-row-mt", "1"]))
         }
-    }
-    
-    // Fallback libx264
-    println!("[codec] Utilisation de libx264 (encodage logiciel)");
-    let codec = "libx264".to_string();
-    let params = vec![
-        "-pix_fmt".to_string(), "yuv420p".to_string(),
-        "-crf".to_string(), "22".to_string(),
-        "-tune".to_string(), "zerolatency".to_string(),
-        "-bf".to_string(), "0".to_string(),
-    ];
-    let mut extra = HashMap::new();
-    extra.insert("preset".to_string(), Some("ultrafast".to_string()));
+    };
     
-    (codec, params, extra)
+    println!("[codec] Utilisation de {} (encodage logiciel)", codec);
+    (codec.to_string(), params, extra)
 }
 
-fn ffmpeg_preprocess_video(src: &str, dst: &str, w: i32, h: i32, fps: i32, prefer_hw: bool, start_ms: Option<i32>, duration_ms: Option<i32>) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let (codec, params, extra) = choose_best_codec(prefer_hw);
-    let exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+// Un échec du lancement ffmpeg d'un prétraitement est rapporté sur le fichier source
+fn preprocess_error(source_path: &str, err: ExportError) -> ExportError {
+    match err {
+        ExportError::EncoderFailed { .. } => ExportError::PreprocessFailed { source_path: source_path.to_string() },
+        other => other,
+    }
+}
 
-    let vf = format!(
-        "scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black,fps={},setsar=1",
-        w, h, w, h, fps
-    );
+/// Prépare un segment de clip de fond : `start_ms` est une position dans la source,
+/// `duration_ms` une durée dans la vidéo exportée (après changement de vitesse).
+///
+/// `tail_ms` prolonge le segment en figeant sa dernière image, pour le fondu vers le clip suivant.
+#[allow(clippy::too_many_arguments)]
+fn ffmpeg_preprocess_video(
+    clip: &BackgroundClip,
+    dst: &str,
+    w: i32,
+    h: i32,
+    fps: i32,
+    prefer_hw: bool,
+    hw_device: Option<&str>,
+    start_ms: Option<i32>,
+    duration_ms: Option<i32>,
+    tail_ms: i32,
+    export_id: &str,
+    chunk_index: Option<i32>,
+    app_handle: &tauri::AppHandle,
+) -> Result<(), ExportError> {
+    let src = clip.path.as_str();
+    let (codec, params, extra) = choose_best_codec(prefer_hw, hw_device);
+    let exe = ffmpeg_binary()?;
 
-    let mut cmd = Command::new(&exe);
+    let fit = clip.fit.unwrap_or_default();
+    let mut vf = format!("{},fps={},setsar=1", fit.filter(w, h), fps);
+    if clip.speed() != 1.0 {
+        vf = format!("setpts=(PTS-STARTPTS)/{},{}", clip.speed(), vf);
+    }
+    if tail_ms > 0 {
+        vf = format!("{},tpad=stop_mode=clone:stop_duration={:.3}", vf, tail_ms as f64 / 1000.0);
+    }
+
+    let mut cmd = vec![exe];
 
     // Si un offset de début est fourni, l'ajouter avant -i pour seek rapide
     if let Some(sms) = start_ms {
-        let s = format!("{:.3}", (sms as f64) / 1000.0);
-        cmd.arg("-ss").arg(s);
+        cmd.extend(["-ss".to_string(), format!("{:.3}", (sms as f64) / 1000.0)]);
     }
 
-    cmd.arg("-y")
-        .arg("-hide_banner")
-        .arg("-loglevel").arg("error")
-        .arg("-i").arg(src);
+    cmd.extend([
+        "-y".to_string(),
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(), "error".to_string(),
+        "-progress".to_string(), "pipe:2".to_string(),
+        "-i".to_string(), src.to_string(),
+    ]);
 
     // Si une durée de découpe est fournie, la limiter
     if let Some(dms) = duration_ms {
-        let d = format!("{:.3}", (dms as f64) / 1000.0);
-        cmd.arg("-t").arg(d);
+        cmd.extend(["-t".to_string(), format!("{:.3}", (dms + tail_ms) as f64 / 1000.0)]);
     }
 
-    cmd.arg("-an")
-        .arg("-vf").arg(&vf)
-        .arg("-pix_fmt").arg("yuv420p")
-        .arg("-c:v").arg(&codec);
-
-    if let Some(Some(preset)) = extra.get("preset") {
-        cmd.arg("-preset").arg(preset);
+    // Les encodeurs VAAPI reçoivent des surfaces GPU à la place du yuv420p
+    match extra.get("hwupload") {
+        Some(Some(upload)) => {
+            cmd.extend(["-an".to_string(), "-vf".to_string(), format!("{},{}", vf, upload)]);
+        }
+        _ => {
+            cmd.extend(["-an".to_string(), "-vf".to_string(), vf, "-pix_fmt".to_string(), "yuv420p".to_string()]);
+        }
     }
+    cmd.extend(["-c:v".to_string(), codec]);
 
-    for param in params {
-        cmd.arg(param);
+    if let Some(Some(preset)) = extra.get("preset") {
+        cmd.extend(["-preset".to_string(), preset.clone()]);
     }
 
-    cmd.arg(dst);
+    cmd.extend(params);
+    cmd.push(dst.to_string());
 
-    // Configurer la commande pour cacher les fenêtres CMD sur Windows
-    configure_command_no_window(&mut cmd);
+    println!("[preproc] ffmpeg {:?} x{} -> {}", fit, clip.speed(), Path::new(dst).file_name().unwrap_or_default().to_string_lossy());
 
-    println!("[preproc] ffmpeg scale+pad -> {}", Path::new(dst).file_name().unwrap_or_default().to_string_lossy());
+    // Un fichier incomplet dans le cache serait réutilisé tel quel au prochain export
+    let token = export_token(export_id);
+    token.track_output(dst);
 
-    let status = cmd.status()?;
-    if !status.success() {
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "FFmpeg preprocessing failed")));
-    }
+    // Durée à encoder : le segment demandé, sinon le reste de la vidéo source
+    let duration_s = match duration_ms {
+        Some(dms) => (dms + tail_ms) as f64 / 1000.0,
+        None => ((ffprobe_duration_sec(src) - start_ms.unwrap_or(0) as f64 / 1000.0) / clip.speed()).max(0.0),
+    };
 
+    run_ffmpeg_with_progress(
+        &cmd,
+        None,
+        export_id,
+        duration_s,
+        (0, 1),
+        ExportPhase::PreprocessingBackgrounds,
+        chunk_index,
+        Some(src),
+        None,
+        app_handle,
+    )
+    .map_err(|e| preprocess_error(src, e))?;
+
+    token.output_done(dst);
     Ok(())
 }
 
-fn create_video_from_image(image_path: &str, output_path: &str, w: i32, h: i32, fps: i32, duration_s: f64, prefer_hw: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
-    
-    // Filtres pour imiter object-cover CSS :
-    // 1. scale pour ajuster la taille en gardant le ratio
-    // 2. crop pour centrer et recadrer si nécessaire
-    let scale_filter = format!("scale={}:{}:force_original_aspect_ratio=increase", w, h);
-    let crop_filter = format!("crop={}:{}:(in_w-{})/2:(in_h-{})/2", w, h, w, h);
-    let video_filter = format!("{},{}", scale_filter, crop_filter);
+/// Transforme une image de fond en vidéo. `offset_s` est le temps d'affichage déjà écoulé
+/// de l'image, pour que le mouvement éventuel continue d'un chunk à l'autre.
+#[allow(clippy::too_many_arguments)]
+fn create_video_from_image(
+    clip: &BackgroundClip,
+    output_path: &str,
+    w: i32,
+    h: i32,
+    fps: i32,
+    duration_s: f64,
+    offset_s: f64,
+    prefer_hw: bool,
+    hw_device: Option<&str>,
+    export_id: &str,
+    chunk_index: Option<i32>,
+    app_handle: &tauri::AppHandle,
+) -> Result<(), ExportError> {
+    let image_path = clip.path.as_str();
+    let ffmpeg_exe = ffmpeg_binary()?;
+    
+    // Cover imite object-cover CSS (scale en gardant le ratio puis crop centré)
+    let fit = clip.fit.unwrap_or(FitMode::Cover);
+    let mut video_filter = match clip.motion {
+        Some(ref motion) => {
+            let kind = motion.resolve_kind(export_id);
+            println!("[preproc][IMG] Mouvement {:?} ({:?}, x{})", kind, motion.easing, motion.speed);
+            format!("{},setsar=1", motion.filter(fit, kind, w, h, fps, offset_s))
+        }
+        None => format!("{},setsar=1", fit.filter(w, h)),
+    };
     
     // Choisir le meilleur codec avec détection automatique
-    let (codec, codec_params, codec_extra) = choose_best_codec(prefer_hw);
+    let (codec, codec_params, codec_extra) = choose_best_codec(prefer_hw, hw_device);
+    if let Some(Some(upload)) = codec_extra.get("hwupload") {
+        video_filter = format!("{},{}", video_filter, upload);
+    }
     
-    let mut cmd = Command::new(&ffmpeg_exe);
-    cmd.args(&[
-        "-y",
-        "-hide_banner", 
-        "-loglevel", "info",
-        "-loop", "1",
-        "-i", image_path,
-        "-vf", &video_filter,
-        "-c:v", &codec,
-        "-r", &fps.to_string(),
-        "-t", &format!("{:.6}", duration_s),
-    ]);
+    let mut cmd = vec![
+        ffmpeg_exe,
+        "-y".to_string(),
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(), "info".to_string(),
+        "-progress".to_string(), "pipe:2".to_string(),
+        "-loop".to_string(), "1".to_string(),
+        // zoompan produit une image par image d'entrée
+        "-framerate".to_string(), fps.to_string(),
+        "-i".to_string(), image_path.to_string(),
+        "-vf".to_string(), video_filter,
+        "-c:v".to_string(), codec.clone(),
+        "-r".to_string(), fps.to_string(),
+        "-t".to_string(), format!("{:.6}", duration_s),
+    ];
     
     // Ajouter le preset si disponible
     if let Some(Some(preset)) = codec_extra.get("preset") {
-        cmd.arg("-preset").arg(preset);
+        cmd.extend(["-preset".to_string(), preset.clone()]);
     }
     
     // Ajouter les paramètres du codec
-    for param in codec_params {
-        cmd.arg(param);
-    }
+    cmd.extend(codec_params);
     
     // Ajouter des paramètres de qualité selon le codec
     if codec == "libx264" {
-        cmd.args(&["-crf", "23"]);
+        cmd.extend(["-crf".to_string(), "23".to_string()]);
     } else if codec.contains("nvenc") {
-        cmd.args(&["-cq", "23"]);
+        cmd.extend(["-cq".to_string(), "23".to_string()]);
     }
     
-    cmd.arg(output_path);
-
-    // Configurer la commande pour cacher les fenêtres CMD sur Windows
-    configure_command_no_window(&mut cmd);
+    cmd.push(output_path.to_string());
 
     println!("[preproc][IMG] Création vidéo depuis image: {} -> {}", image_path, output_path);
-    println!("[preproc][IMG] Commande: {:?}", cmd);
-
-    let status = cmd.status()?;
-    if !status.success() {
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "FFmpeg image-to-video failed")));
-    }
 
+    let token = export_token(export_id);
+    token.track_output(output_path);
+
+    run_ffmpeg_with_progress(
+        &cmd,
+        None,
+        export_id,
+        duration_s,
+        (0, 1),
+        ExportPhase::PreprocessingBackgrounds,
+        chunk_index,
+        Some(image_path),
+        None,
+        app_handle,
+    )
+    .map_err(|e| preprocess_error(image_path, e))?;
+
+    token.output_done(output_path);
     Ok(())
 }
 
+/// Clé de cache d'une image de fond ; avec un mouvement, elle dépend aussi de la position dans l'image
+fn image_cache_params(clip: &BackgroundClip, w: i32, h: i32, fps: i32, duration_s: f64, offset_ms: i32, export_id: &str) -> String {
+    let mut params = format!("{}x{}-{}-dur{}-{:?}", w, h, fps, duration_s, clip.fit);
+    if let Some(ref motion) = clip.motion {
+        params.push_str(&format!(
+            "-{:?}-x{}-{:?}-at{}",
+            motion.resolve_kind(export_id), motion.speed, motion.easing, offset_ms
+        ));
+    }
+    params
+}
+
 fn is_image_file(path: &str) -> bool {
     let path_lower = path.to_lowercase();
     path_lower.ends_with(".jpg") || path_lower.ends_with(".jpeg") || 
@@ -447,59 +855,91 @@ fn is_image_file(path: &str) -> bool {
     path_lower.ends_with(".tiff") || path_lower.ends_with(".tif")
 }
 
-fn preprocess_background_videos(video_paths: &[String], w: i32, h: i32, fps: i32, prefer_hw: bool, start_time_ms: i32, duration_ms: Option<i32>) -> Vec<String> {
+/// Prépare les vidéos et images de fond à la taille de l'export.
+///
+/// Les clips sont placés bout à bout sur la timeline de l'export. Une image dure `duration_ms`
+/// (jusqu'à la fin sans durée) ; un clip avec `crossfade_ms` commence par un fondu depuis le
+/// précédent, qui est prolongé d'autant pour ne pas décaler la suite.
+///
+/// Les échecs de prétraitement sont tolérés (la vidéo source est alors utilisée telle quelle),
+/// seule l'annulation de l'export est renvoyée comme erreur.
+#[allow(clippy::too_many_arguments)]
+fn preprocess_background_videos(
+    clips: &[BackgroundClip],
+    w: i32,
+    h: i32,
+    fps: i32,
+    prefer_hw: bool,
+    hw_device: Option<&str>,
+    start_time_ms: i32,
+    duration_ms: Option<i32>,
+    export_id: &str,
+    project_id: Option<&str>,
+    chunk_index: Option<i32>,
+    app_handle: &tauri::AppHandle,
+) -> Result<Vec<PreparedBackground>, ExportError> {
     let mut out_paths = Vec::new();
-    let cache_dir = std::env::temp_dir().join("qurancaption-preproc");
-    fs::create_dir_all(&cache_dir).ok();
-
-    // Cas spécial : une seule image
-    if video_paths.len() == 1 && is_image_file(&video_paths[0]) {
-        let image_path = &video_paths[0];
-        let duration_s = if let Some(dur_ms) = duration_ms { 
-            dur_ms as f64 / 1000.0 
-        } else { 
-            30.0 // Durée par défaut si non spécifiée
-        };
-
-        // Construire un nom de cache unique pour l'image
-        let hash_input = format!("{}-{}x{}-{}-dur{}", image_path, w, h, fps, duration_s);
-        let stem_hash = format!("{:x}", md5::compute(hash_input.as_bytes()));
-        let stem_hash = &stem_hash[..10.min(stem_hash.len())];
-        let dst = cache_dir.join(format!("img-bg-{}-{}x{}-{}.mp4", stem_hash, w, h, fps));
-
-        if !dst.exists() {
-            match create_video_from_image(image_path, &dst.to_string_lossy(), w, h, fps, duration_s, prefer_hw) {
-                Ok(_) => {},
-                Err(e) => {
-                    println!("[preproc][ERREUR] Impossible de créer la vidéo à partir de l'image: {:?}", e);
-                    return vec![];
-                }
-            }
+    fs::create_dir_all(preproc_cache::cache_dir()).ok();
+    preproc_cache::enforce_limit();
+
+    // Durée de chaque clip dans la vidéo exportée (None = jusqu'à la fin de l'export)
+    let token = export_token(export_id);
+    let mut spans: Vec<(Option<i64>, i64)> = Vec::new();
+    for clip in clips {
+        if is_image_file(&clip.path) {
+            spans.push((clip.duration_ms.map(|d| d.max(0) as i64), 0));
+        } else {
+            let source_ms = (token.source_duration_s(&clip.path) * 1000.0).round() as i64;
+            let len = if clip.loop_until_end { None } else { Some(clip.timeline_len_ms(source_ms)) };
+            spans.push((len, source_ms));
         }
-
-        out_paths.push(dst.to_string_lossy().to_string());
-        return out_paths;
-    }
-
-    // Calculer les durées (ms) de chaque vidéo
-    let mut video_durations_ms: Vec<i64> = Vec::new();
-    for p in video_paths {
-        let d = (ffprobe_duration_sec(p) * 1000.0).round() as i64;
-        video_durations_ms.push(d);
     }
 
     // Limite de la plage demandée
     let limit_ms: i64 = if let Some(dur) = duration_ms { dur as i64 } else { i64::MAX };
+    let range_end = (start_time_ms as i64).saturating_add(limit_ms);
+
+    // Parcourir les clips et extraire uniquement les segments pertinents.
+    // Les positions sont exprimées dans la vidéo exportée (trim et vitesse appliqués) ;
+    // un clip sans position sur la timeline commence à la fin du précédent.
+    let mut cursor: i64 = 0;
+    // Noir en attente, inséré avant le prochain segment préparé
+    let mut gap_ms: i64 = 0;
+    // Prolongation donnée au dernier segment préparé, pour le fondu vers le suivant
+    let mut prev_tail_ms: i64 = 0;
+    for (idx, clip) in clips.iter().enumerate() {
+        let (own_len, source_ms) = spans[idx];
+        let cum_start = clip.timeline_start_ms.map(|s| s.max(0) as i64).unwrap_or(cursor);
+
+        // Un intervalle vide avant le clip devient du noir
+        if cum_start > cursor {
+            let gap_end = cum_start.min(range_end);
+            let gap_start = cursor.max(start_time_ms as i64);
+            gap_ms += (gap_end - gap_start).max(0);
+            prev_tail_ms = 0;
+        }
 
-    // Parcourir les vidéos et extraire uniquement les segments pertinents
-    let mut cum_start: i64 = 0;
-    for (idx, p) in video_paths.iter().enumerate() {
-        let vid_len = video_durations_ms.get(idx).cloned().unwrap_or(0);
-        let cum_end = cum_start + vid_len;
+        // La fin posée sur la timeline et le début du clip suivant bornent le clip (sauf boucle)
+        let len = if own_len.is_none() && !is_image_file(&clip.path) {
+            None
+        } else {
+            let next_start = clips.get(idx + 1).and_then(|n| n.timeline_start_ms).map(|s| s.max(0) as i64);
+            let bound = clip.timeline_end_ms.map(|e| e as i64).into_iter().chain(next_start).min().map(|b| b - cum_start);
+            match (own_len, bound) {
+                (Some(l), Some(b)) => Some(l.min(b)),
+                (l, b) => l.or(b),
+            }
+        };
+        if len.map(|l| l <= 0).unwrap_or(false) {
+            continue;
+        }
+        let clip_len = len.unwrap_or(i64::MAX - cum_start);
+        let cum_end = cum_start.saturating_add(clip_len);
 
-        // Si la vidéo se termine avant le début recherché, on l'ignore complètement
+        // Si le clip se termine avant le début recherché, on l'ignore complètement
         if cum_end <= start_time_ms as i64 {
-            cum_start = cum_end;
+            cursor = cum_end;
+            prev_tail_ms = 0;
             continue;
         }
 
@@ -509,54 +949,152 @@ fn preprocess_background_videos(video_paths: &[String], w: i32, h: i32, fps: i32
             break;
         }
 
-        // Déterminer le début à l'intérieur de cette vidéo
-        let start_within = if start_time_ms as i64 > cum_start { (start_time_ms as i64 - cum_start) } else { 0 };
+        // Déterminer le début à l'intérieur de ce clip
+        let start_within = (start_time_ms as i64 - cum_start).max(0);
+
+        // Le fondu n'a lieu que si le segment précédent a été prolongé pour lui
+        let fade_in_s = if start_within == 0 { prev_tail_ms as f64 / 1000.0 } else { 0.0 };
+        prev_tail_ms = 0;
+
+        // Clip vidéo en boucle : un seul passage est prétraité, puis rejoué en boucle par le rendu final
+        if len.is_none() && !is_image_file(&clip.path) {
+            let cycle_ms = clip.timeline_len_ms(source_ms);
+            if cycle_ms <= 0 {
+                break;
+            }
+            let (trim_in, _) = clip.trim_range_ms(source_ms);
+            let dst = preproc_cache::entry_path(
+                "bg-loop",
+                &clip.path,
+                &format!("{}x{}-{}-{}", w, h, fps, clip.cache_params()),
+                &format!("{}x{}-{}", w, h, fps),
+            );
+            let loop_from_s = (start_within % cycle_ms) as f64 / 1000.0;
+
+            if !preproc_cache::touch(&dst, export_id, project_id) {
+                match ffmpeg_preprocess_video(clip, &dst.to_string_lossy(), w, h, fps, prefer_hw, hw_device, Some(trim_in as i32), Some(cycle_ms as i32), 0, export_id, chunk_index, app_handle) {
+                    Ok(_) => preproc_cache::record(&dst, &clip.path, export_id, project_id),
+                    Err(e @ ExportError::Cancelled { .. }) => return Err(e),
+                    Err(e) => {
+                        println!("[preproc][ERREUR] {:?}", e);
+                        out_paths.push(PreparedBackground { path: clip.path.clone(), loop_from_s: None, fade_in_s: 0.0, gap_before_s: std::mem::take(&mut gap_ms) as f64 / 1000.0 });
+                        break;
+                    }
+                }
+            }
+
+            println!("[preproc] Clip en boucle ({:.1}s), départ à {:.1}s", cycle_ms as f64 / 1000.0, loop_from_s);
+            out_paths.push(PreparedBackground {
+                path: dst.to_string_lossy().to_string(),
+                loop_from_s: Some(loop_from_s),
+                fade_in_s,
+                gap_before_s: std::mem::take(&mut gap_ms) as f64 / 1000.0,
+            });
+            break;
+        }
 
-        // Durée restante à prendre dans cette vidéo
+        // Durée restante à prendre dans ce clip (30 s pour une image seule sans durée connue)
         let elapsed_from_start = (cum_start + start_within) - (start_time_ms as i64);
         let remaining_needed = (limit_ms - elapsed_from_start).max(0);
-        let take_ms = remaining_needed.min(vid_len - start_within);
+        let take_ms = if len.is_none() && limit_ms == i64::MAX {
+            30_000
+        } else {
+            remaining_needed.min(clip_len - start_within)
+        };
 
         if take_ms <= 0 {
-            cum_start = cum_end;
+            cursor = cum_end;
             continue;
         }
 
-        // Construire un nom de cache unique qui inclut les offsets
-        let hash_input = format!("{}-{}x{}-{}-start{}-len{}", p, w, h, fps, start_within, take_ms);
-        let stem_hash = format!("{:x}", md5::compute(hash_input.as_bytes()));
-        let stem_hash = &stem_hash[..10.min(stem_hash.len())];
-        let dst = cache_dir.join(format!("bg-{}-{}x{}-{}.mp4", stem_hash, w, h, fps));
-
-        if !dst.exists() {
-            // Appeler ffmpeg_preprocess_video avec les offsets locaux
-            match ffmpeg_preprocess_video(p, &dst.to_string_lossy(), w, h, fps, prefer_hw, Some(start_within as i32), Some(take_ms as i32)) {
-                Ok(_) => {},
-                Err(e) => {
-                    println!("[preproc][ERREUR] {:?}", e);
-                    // En cas d'échec, utiliser la vidéo originale (et laisser ffmpeg final gérer le trim)
-                    out_paths.push(p.clone());
-                    cum_start = cum_end;
-                    continue;
+        // Le clip suivant commence dans la plage avec un fondu : ce segment est prolongé d'autant
+        let ends_naturally = len.is_some() && start_within + take_ms == clip_len;
+        let tail_ms = match clips.get(idx + 1) {
+            Some(next) if ends_naturally && cum_end < range_end && next.timeline_start_ms.map(|s| s.max(0) as i64).unwrap_or(cum_end) == cum_end => {
+                (next.crossfade_ms.unwrap_or(0) as i64).clamp(0, take_ms)
+            }
+            _ => 0,
+        };
+
+        let prepared = if is_image_file(&clip.path) {
+            let duration_s = (take_ms + tail_ms) as f64 / 1000.0;
+            let dst = preproc_cache::entry_path(
+                "img-bg",
+                &clip.path,
+                &image_cache_params(clip, w, h, fps, duration_s, start_within as i32, export_id),
+                &format!("{}x{}-{}", w, h, fps),
+            );
+
+            if !preproc_cache::touch(&dst, export_id, project_id) {
+                match create_video_from_image(clip, &dst.to_string_lossy(), w, h, fps, duration_s, start_within as f64 / 1000.0, prefer_hw, hw_device, export_id, chunk_index, app_handle) {
+                    Ok(_) => preproc_cache::record(&dst, &clip.path, export_id, project_id),
+                    Err(e @ ExportError::Cancelled { .. }) => return Err(e),
+                    Err(e) => {
+                        // Une image non convertie est remplacée par du noir
+                        println!("[preproc][ERREUR] Impossible de créer la vidéo à partir de l'image: {:?}", e);
+                        gap_ms += take_ms;
+                        cursor = cum_end;
+                        continue;
+                    }
                 }
             }
-        }
+            dst
+        } else {
+            // Position de départ dans la source
+            let (trim_in, _) = clip.trim_range_ms(source_ms);
+            let source_start = trim_in + (start_within as f64 * clip.speed()).round() as i64;
+
+            // Construire un nom de cache unique qui inclut les offsets
+            let dst = preproc_cache::entry_path(
+                "bg",
+                &clip.path,
+                &format!("{}x{}-{}-start{}-len{}-tail{}-{}", w, h, fps, start_within, take_ms, tail_ms, clip.cache_params()),
+                &format!("{}x{}-{}", w, h, fps),
+            );
+
+            if !preproc_cache::touch(&dst, export_id, project_id) {
+                // Appeler ffmpeg_preprocess_video avec les offsets locaux
+                match ffmpeg_preprocess_video(clip, &dst.to_string_lossy(), w, h, fps, prefer_hw, hw_device, Some(source_start as i32), Some(take_ms as i32), tail_ms as i32, export_id, chunk_index, app_handle) {
+                    Ok(_) => preproc_cache::record(&dst, &clip.path, export_id, project_id),
+                    Err(e @ ExportError::Cancelled { .. }) => return Err(e),
+                    Err(e) => {
+                        println!("[preproc][ERREUR] {:?}", e);
+                        // En cas d'échec, utiliser la vidéo originale (et laisser ffmpeg final gérer le trim)
+                        out_paths.push(PreparedBackground {
+                            path: clip.path.clone(),
+                            loop_from_s: None,
+                            fade_in_s: 0.0,
+                            gap_before_s: std::mem::take(&mut gap_ms) as f64 / 1000.0,
+                        });
+                        cursor = cum_end;
+                        continue;
+                    }
+                }
+            }
+            dst
+        };
 
-        out_paths.push(dst.to_string_lossy().to_string());
+        out_paths.push(PreparedBackground {
+            path: prepared.to_string_lossy().to_string(),
+            loop_from_s: None,
+            fade_in_s,
+            gap_before_s: std::mem::take(&mut gap_ms) as f64 / 1000.0,
+        });
+        prev_tail_ms = tail_ms;
 
-        // Si on a atteint la limite, on arrête
+        // Si on a atteint la limite (ou rempli la fin de l'export), on arrête
         let elapsed_total = (cum_start + start_within + take_ms) - (start_time_ms as i64);
-        if elapsed_total >= limit_ms {
+        if elapsed_total >= limit_ms || len.is_none() {
             break;
         }
 
-        cum_start = cum_end;
+        cursor = cum_end;
     }
 
-    out_paths
+    Ok(out_paths)
 }
 
-fn ffprobe_duration_sec(path: &str) -> f64 {
+pub(crate) fn ffprobe_duration_sec(path: &str) -> f64 {
     let exe = resolve_ffprobe_binary();
     
     let mut cmd = Command::new(&exe);
@@ -579,9 +1117,66 @@ fn ffprobe_duration_sec(path: &str) -> f64 {
     txt.parse::<f64>().unwrap_or(0.0)
 }
 
+fn ffprobe_video_size(path: &str) -> Option<(u32, u32)> {
+    let exe = resolve_ffprobe_binary();
+    
+    let mut cmd = Command::new(&exe);
+    cmd.args([
+        "-v", "error",
+        "-select_streams", "v:0",
+        "-show_entries", "stream=width,height",
+        "-of", "csv=s=x:p=0",
+        path,
+    ]);
+    
+    // Configurer la commande pour cacher les fenêtres CMD sur Windows
+    configure_command_no_window(&mut cmd);
+    
+    let output = cmd.output().ok()?;
+    let txt = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let (w, h) = txt.split_once('x')?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+// Réglages de débit par défaut des encodeurs, remplacés par ceux du profil
+const RATE_CONTROL_ARGS: [&str; 11] = [
+    "-crf", "-b:v", "-maxrate", "-bufsize", "-rc", "-cq", "-qp", "-qp_i", "-qp_p", "-qp_b", "-global_quality",
+];
+
+/// Fusionne les arguments du profil dans ceux de l'encodeur (paires `-option valeur`) : le débit
+/// par défaut et les options redéfinies par le profil sont retirés, le reste (pix_fmt, `-row-mt`,
+/// `-deadline`, périphérique, ...) est gardé.
+fn merge_codec_args(params: &[String], profile_args: Vec<String>) -> Vec<String> {
+    let overridden: Vec<&str> = profile_args.iter().step_by(2).map(|a| a.as_str()).collect();
+    let mut merged: Vec<String> = params
+        .chunks(2)
+        .filter(|pair| !RATE_CONTROL_ARGS.contains(&pair[0].as_str()) && !overridden.contains(&pair[0].as_str()))
+        .flatten()
+        .cloned()
+        .collect();
+    merged.extend(profile_args);
+    merged
+}
+
+/// Remplace les réglages de débit par défaut de l'encodeur par ceux du profil
+fn apply_encoding_profile(
+    profile: &EncodingProfile,
+    vcodec: &str,
+    fps: i32,
+    vparams: &mut Vec<String>,
+    vextra: &mut HashMap<String, Option<String>>,
+) {
+    *vparams = merge_codec_args(vparams, profile.video_args(vcodec, fps));
+    if let Some(preset) = profile.preset_for(vcodec) {
+        vextra.insert("preset".to_string(), preset);
+    }
+    println!("[codec] Profil d'encodage appliqué: {:?}", profile.rate_control);
+}
+
 #[allow(clippy::too_many_arguments)]
 fn build_and_run_ffmpeg_filter_complex(
     export_id: &str,
+    project_id: Option<&str>,
     out_path: &str,
     image_paths: &[String],
     timestamps_ms: &[i32],
@@ -589,24 +1184,29 @@ fn build_and_run_ffmpeg_filter_complex(
     fps: i32,
     fade_duration_ms: i32,
     start_time_ms: i32,
-    audio_paths: &[String],
-    bg_videos: &[String],
+    audio_layers: &[AudioLayer],
+    bg_videos: &[BackgroundClip],
     prefer_hw: bool,
     imgs_cwd: Option<&str>,
     duration_ms: Option<i32>,
     chunk_index: Option<i32>,
+    soft_subtitles: Option<&SoftSubtitleOptions>,
+    overlay_format: Option<OverlayFormat>,
+    output_format: &OutputFormat,
+    profile: Option<&EncodingProfile>,
+    progress_tracker: Option<&ExportProgressTracker>,
     app_handle: tauri::AppHandle,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+) -> Result<Option<LoudnessMeasurement>, ExportError> {
     let (w, h) = target_size;
     let fade_s = (fade_duration_ms as f64 / 1000.0).max(0.0);
     let start_s = (start_time_ms as f64 / 1000.0).max(0.0);
     
     let n = image_paths.len();
     if n == 0 {
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Aucune image fournie")));
+        return Err(ExportError::invalid_input("Aucune image fournie"));
     }
     if n != timestamps_ms.len() {
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Le nombre d'images ne correspond pas au nombre de timestamps")));
+        return Err(ExportError::invalid_input("Le nombre d'images ne correspond pas au nombre de timestamps"));
     }
     
     let tail_ms = fade_duration_ms.max(1000);
@@ -634,23 +1234,58 @@ fn build_and_run_ffmpeg_filter_complex(
         acc += d;
     }
     
-    let (vcodec, vparams, vextra) = choose_best_codec(prefer_hw);
+    // Les formats overlay imposent leur propre codec (avec alpha)
+    let audio_only = output_format.audio_only && overlay_format.is_none();
+    let two_pass = profile.map(|p| p.target_size_mb().is_some()).unwrap_or(false);
+    let (vcodec, mut vparams, mut vextra) = if overlay_format.is_some() || audio_only {
+        (String::new(), Vec::new(), HashMap::new())
+    } else {
+        choose_codec(output_format.codec, prefer_hw, output_format.hw_device.as_deref(), two_pass)
+    };
     
     let mut pre_videos = Vec::new();
-    if !bg_videos.is_empty() {
-        pre_videos = preprocess_background_videos(bg_videos, w, h, fps, prefer_hw, start_time_ms, duration_ms);
+    if !bg_videos.is_empty() && overlay_format.is_none() && !audio_only {
+        emit_export_phase(&app_handle, export_id, chunk_index, ExportPhase::PreprocessingBackgrounds);
+        pre_videos = preprocess_background_videos(bg_videos, w, h, fps, prefer_hw, output_format.hw_device.as_deref(), start_time_ms, duration_ms, export_id, project_id, chunk_index, &app_handle)?;
     }
     
-    let mut total_bg_s = 0.0;
-    for p in &pre_videos {
-        total_bg_s += ffprobe_duration_sec(p);
-    }
+    // Un fond rejoué en boucle couvre toute la durée ; les fondus chevauchent deux fonds
+    let bg_durations_s: Vec<f64> = pre_videos
+        .iter()
+        .map(|p| match p.loop_from_s {
+            Some(_) => f64::INFINITY,
+            None => ffprobe_duration_sec(&p.path),
+        })
+        .collect();
+    let total_bg_s: f64 = bg_durations_s.iter().sum::<f64>()
+        + pre_videos.iter().map(|p| p.gap_before_s - p.fade_in_s).sum::<f64>();
+    
+    // Clips audio de chaque couche placés sur la timeline, limités à la plage exportée
+    let audio_durations = audio::source_durations(audio_layers, export_id);
+    let audio_placements: Vec<audio::LayerPlacement> = audio_layers.iter().map(|l| l.place(start_s, duration_s, &audio_durations)).collect();
+    let have_audio = audio_placements.iter().any(|p| !p.clips.is_empty())
+        && overlay_format.map(|f| f.supports_audio()).unwrap_or(true);
+    
+    if audio_only && !have_audio {
+        return Err(ExportError::invalid_input("Aucun audio à exporter sur cette plage"));
+    }
+    
+    // Normalisation du volume : 1re passe sur ce chunk, sauf si tout l'export a déjà été mesuré
+    let loudness = match output_format.loudness.as_ref().filter(|_| have_audio) {
+        Some(options) => match options.measured {
+            Some(measured) => Some((options, measured)),
+            None => loudness::measure(export_id, audio_layers, &audio_durations, options, start_s, duration_s, chunk_index, &app_handle)?
+                .map(|measured| (options, measured)),
+        },
+        None => None,
+    };
     
-    let mut total_audio_s = 0.0;
-    for p in audio_paths {
-        total_audio_s += ffprobe_duration_sec(p);
+    // Le profil d'encodage remplace les réglages de débit par défaut de l'encodeur
+    let profile = profile.map(|p| p.for_duration(duration_s, have_audio, audio_only)).transpose()?;
+    let profile = profile.as_ref();
+    if let Some(profile) = profile.filter(|_| !vcodec.is_empty()) {
+        apply_encoding_profile(profile, &vcodec, fps, &mut vparams, &mut vextra);
     }
-    let have_audio = !audio_paths.is_empty() && start_s < total_audio_s - 1e-6;
     
     // Préparer le fichier concat
     let base_dir = if let Some(cwd) = imgs_cwd {
@@ -672,10 +1307,12 @@ fn build_and_run_ffmpeg_filter_complex(
     }
     writeln!(concat_file, "file '{}'", image_paths[n - 1])?;
     
+    let token = export_token(export_id);
+    token.track_temp_file(&concat_path);
     println!("[concat] Fichier ffconcat -> {:?}", concat_path);
     
     let mut cmd = Vec::new();
-    let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+    let ffmpeg_exe = ffmpeg_binary()?;
     cmd.extend_from_slice(&[
         ffmpeg_exe.clone(),
         "-y".to_string(),
@@ -687,168 +1324,224 @@ fn build_and_run_ffmpeg_filter_complex(
     
     let mut current_idx = 0;
     
-    // Entrée unique: concat demuxer
-    let concat_name = if imgs_cwd.is_some() {
-        concat_path.file_name().unwrap().to_string_lossy().to_string()
-    } else {
-        concat_path.to_string_lossy().to_string()
-    };
+    if !audio_only {
+        // Entrée unique: concat demuxer
+        let concat_name = if imgs_cwd.is_some() {
+            concat_path.file_name().unwrap().to_string_lossy().to_string()
+        } else {
+            concat_path.to_string_lossy().to_string()
+        };
     
-    cmd.extend_from_slice(&[
-        "-safe".to_string(), "0".to_string(),
-        "-f".to_string(), "concat".to_string(),
-        "-i".to_string(), concat_name,
-    ]);
-    current_idx = 1;
+        cmd.extend_from_slice(&[
+            "-safe".to_string(), "0".to_string(),
+            "-f".to_string(), "concat".to_string(),
+            "-i".to_string(), concat_name,
+        ]);
+        current_idx = 1;
+    }
     
-    // Entrées vidéos de fond
-    let bg_start_idx = current_idx;
+    // Entrées vidéos de fond, précédées du noir des intervalles vides de la timeline
+    let mut bg_idx = Vec::new();
+    let mut gap_idx = Vec::new();
     for p in &pre_videos {
-        cmd.extend_from_slice(&["-i".to_string(), p.clone()]);
+        if p.gap_before_s > 1e-6 {
+            cmd.extend_from_slice(&[
+                "-f".to_string(), "lavfi".to_string(),
+                "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, p.gap_before_s),
+            ]);
+            gap_idx.push(Some(current_idx));
+            current_idx += 1;
+        } else {
+            gap_idx.push(None);
+        }
+        if p.loop_from_s.is_some() {
+            cmd.extend_from_slice(&["-stream_loop".to_string(), "-1".to_string()]);
+        }
+        cmd.extend_from_slice(&["-i".to_string(), p.path.clone()]);
+        bg_idx.push(current_idx);
         current_idx += 1;
     }
     
     // Entrées audio
     let audio_start_idx = current_idx;
     if have_audio {
-        for p in audio_paths {
-            cmd.extend_from_slice(&["-i".to_string(), p.clone()]);
+        for p in audio_placements.iter().flat_map(|p| &p.clips) {
+            cmd.extend_from_slice(&["-i".to_string(), p.path.clone()]);
             current_idx += 1;
         }
     }
     
     let mut filter_lines = Vec::new();
+    // Ligne de [vout] à compléter par l'upload GPU si l'encodeur en demande un
+    let mut vout_line = None;
+    
+    if !audio_only {
+        // Base: préparer le flux vidéo unique [0:v]
+        let mut split_outputs = String::new();
+        for i in 0..n {
+            split_outputs.push_str(&format!("[b{}]", i));
+        }
     
-    // Base: préparer le flux vidéo unique [0:v]
-    let mut split_outputs = String::new();
-    for i in 0..n {
-        split_outputs.push_str(&format!("[b{}]", i));
-    }
-    
-    filter_lines.push(format!(
-        "[0:v]format=rgba,scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setpts=PTS-STARTPTS,setsar=1,format=yuva444p,split={}{}",
-        w, h, w, h, fps, n, split_outputs
-    ));
-    
-    // Pour chaque segment, extraire la fenêtre temporelle et séparer couleur/alpha
-    for i in 0..n {
-        let s = starts_s[i];
-        let e = s + durations_s[i];
         filter_lines.push(format!(
-            "[b{}]trim=start={:.6}:end={:.6},setpts=PTS-STARTPTS,fps={},split=2[s{}witha][s{}foralpha]",
-            i, s, e, fps, i, i
+            "[0:v]format=rgba,scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setpts=PTS-STARTPTS,setsar=1,format=yuva444p,split={}{}",
+            w, h, w, h, fps, n, split_outputs
         ));
-        filter_lines.push(format!("[s{}foralpha]extractplanes=a[s{}a]", i, i));
-        filter_lines.push(format!("[s{}witha]format=yuv444p[s{}c]", i, i));
-    }
-    
-    // Chaîne xfade pour couleur et alpha séparément
-    let mut curr_c = "s0c".to_string();
-    let mut curr_a = "s0a".to_string();
-    let mut curr_duration = durations_s[0];
-    
-    for i in 0..(n - 1) {
-        let fade_i = durations_s[i].min(fade_s);
-        if fade_i <= 1e-6 {
-            let out_c = format!("cc{}", i);
-            let out_a = format!("ca{}", i);
-            filter_lines.push(format!("[{}][s{}c]concat=n=2:v=1:a=0[{}]", curr_c, i + 1, out_c));
-            filter_lines.push(format!("[{}][s{}a]concat=n=2:v=1:a=0[{}]", curr_a, i + 1, out_a));
-            curr_c = out_c;
-            curr_a = out_a;
-            curr_duration += durations_s[i + 1];
-        } else {
-            let out_c = format!("xc{}", i);
-            let out_a = format!("xa{}", i);
-            let offset = (curr_duration - fade_i).max(0.0);
-            filter_lines.push(format!(
-                "[{}][s{}c]xfade=transition=fade:duration={:.6}:offset={:.6}[{}]",
-                curr_c, i + 1, fade_i, offset, out_c
-            ));
+    
+        // Pour chaque segment, extraire la fenêtre temporelle et séparer couleur/alpha
+        for i in 0..n {
+            let s = starts_s[i];
+            let e = s + durations_s[i];
             filter_lines.push(format!(
-                "[{}][s{}a]xfade=transition=fade:duration={:.6}:offset={:.6}[{}]",
-                curr_a, i + 1, fade_i, offset, out_a
+                "[b{}]trim=start={:.6}:end={:.6},setpts=PTS-STARTPTS,fps={},split=2[s{}witha][s{}foralpha]",
+                i, s, e, fps, i, i
             ));
-            curr_c = out_c;
-            curr_a = out_a;
-            curr_duration = curr_duration + durations_s[i + 1] - fade_i;
+            filter_lines.push(format!("[s{}foralpha]extractplanes=a[s{}a]", i, i));
+            filter_lines.push(format!("[s{}witha]format=yuv444p[s{}c]", i, i));
         }
-    }
     
-    // Reconstituer RGBA pour l'overlay final
-    filter_lines.push(format!("[{}][{}]alphamerge,format=yuva444p[overlay]", curr_c, curr_a));
+        // Chaîne xfade pour couleur et alpha séparément
+        let mut curr_c = "s0c".to_string();
+        let mut curr_a = "s0a".to_string();
+        let mut curr_duration = durations_s[0];
+    
+        for i in 0..(n - 1) {
+            let fade_i = durations_s[i].min(fade_s);
+            if fade_i <= 1e-6 {
+                let out_c = format!("cc{}", i);
+                let out_a = format!("ca{}", i);
+                filter_lines.push(format!("[{}][s{}c]concat=n=2:v=1:a=0[{}]", curr_c, i + 1, out_c));
+                filter_lines.push(format!("[{}][s{}a]concat=n=2:v=1:a=0[{}]", curr_a, i + 1, out_a));
+                curr_c = out_c;
+                curr_a = out_a;
+                curr_duration += durations_s[i + 1];
+            } else {
+                let out_c = format!("xc{}", i);
+                let out_a = format!("xa{}", i);
+                let offset = (curr_duration - fade_i).max(0.0);
+                filter_lines.push(format!(
+                    "[{}][s{}c]xfade=transition=fade:duration={:.6}:offset={:.6}[{}]",
+                    curr_c, i + 1, fade_i, offset, out_c
+                ));
+                filter_lines.push(format!(
+                    "[{}][s{}a]xfade=transition=fade:duration={:.6}:offset={:.6}[{}]",
+                    curr_a, i + 1, fade_i, offset, out_a
+                ));
+                curr_c = out_c;
+                curr_a = out_a;
+                curr_duration = curr_duration + durations_s[i + 1] - fade_i;
+            }
+        }
     
-    // Construction de la vidéo de fond [bg]
-    let avail_bg_after = total_bg_s;
-    let need_black_full = pre_videos.is_empty() || avail_bg_after <= 1e-6;
+        // Reconstituer RGBA pour l'overlay final
+        filter_lines.push(format!("[{}][{}]alphamerge,format=yuva444p[overlay]", curr_c, curr_a));
     
-    let bg_label = if need_black_full {
-        let color_full_idx = current_idx;
-        cmd.extend_from_slice(&[
-            "-f".to_string(), "lavfi".to_string(),
-            "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, duration_s),
-        ]);
-        current_idx += 1;
-        format!("{}:v", color_full_idx)
-    } else {
-        let prev = if pre_videos.len() > 1 {
-            let mut ins = String::new();
-            for i in 0..pre_videos.len() {
-                ins.push_str(&format!("[{}:v]", bg_start_idx + i));
-            }
-            filter_lines.push(format!("{}concat=n={}:v=1:a=0[bgcat]", ins, pre_videos.len()));
-            "bgcat".to_string()
+        if let Some(format) = overlay_format {
+            // Export overlay seul : pas de fond, le canal alpha est conservé
+            filter_lines.push(format!("[overlay]format={}[vout]", format.pix_fmt()));
         } else {
-            format!("{}:v", bg_start_idx)
-        };
+            // Construction de la vidéo de fond [bg]
+            let avail_bg_after = total_bg_s;
+            let need_black_full = pre_videos.is_empty() || avail_bg_after <= 1e-6;
+    
+            let bg_label = if need_black_full {
+                let color_full_idx = current_idx;
+                cmd.extend_from_slice(&[
+                    "-f".to_string(), "lavfi".to_string(),
+                    "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, duration_s),
+                ]);
+                current_idx += 1;
+                format!("{}:v", color_full_idx)
+            } else {
+                // Segments du fond dans l'ordre : (flux, durée, fondu depuis le précédent).
+                // Le clip en boucle démarre à la position correspondant au début de l'export.
+                let mut bg_inputs = Vec::new();
+                let mut seg_durations_s = Vec::new();
+                let mut seg_fades_s = Vec::new();
+                for (i, p) in pre_videos.iter().enumerate() {
+                    if let Some(idx) = gap_idx[i] {
+                        bg_inputs.push(format!("{}:v", idx));
+                        seg_durations_s.push(p.gap_before_s);
+                        seg_fades_s.push(0.0);
+                    }
+                    match p.loop_from_s {
+                        Some(from_s) if from_s > 0.0 => {
+                            filter_lines.push(format!("[{}:v]trim=start={:.6},setpts=PTS-STARTPTS[bgloop{}]", bg_idx[i], from_s, i));
+                            bg_inputs.push(format!("bgloop{}", i));
+                        }
+                        _ => bg_inputs.push(format!("{}:v", bg_idx[i])),
+                    }
+                    seg_durations_s.push(bg_durations_s[i]);
+                    seg_fades_s.push(p.fade_in_s);
+                }
+                
+                let has_fades = seg_fades_s.iter().any(|&f| f > 0.0);
+                let prev = if has_fades {
+                    // xfade exige des flux de même base de temps, cadence et format
+                    for (i, label) in bg_inputs.iter_mut().enumerate() {
+                        filter_lines.push(format!(
+                            "[{}]settb=AVTB,setpts=PTS-STARTPTS,fps={},format=yuv420p,setsar=1[bgn{}]",
+                            label, fps, i
+                        ));
+                        *label = format!("bgn{}", i);
+                    }
+                    
+                    // Fondu enchaîné quand le fond suivant en demande un, coupe franche sinon
+                    let mut acc = bg_inputs[0].clone();
+                    let mut acc_len_s = seg_durations_s[0];
+                    for i in 1..bg_inputs.len() {
+                        let fade_s = seg_fades_s[i];
+                        if fade_s > 0.0 {
+                            filter_lines.push(format!(
+                                "[{}][{}]xfade=transition=fade:duration={:.6}:offset={:.6}[bgx{}]",
+                                acc, bg_inputs[i], fade_s, (acc_len_s - fade_s).max(0.0), i
+                            ));
+                        } else {
+                            filter_lines.push(format!("[{}][{}]concat=n=2:v=1:a=0[bgx{}]", acc, bg_inputs[i], i));
+                        }
+                        acc = format!("bgx{}", i);
+                        acc_len_s += seg_durations_s[i] - fade_s;
+                    }
+                    acc
+                } else if bg_inputs.len() > 1 {
+                    let ins: String = bg_inputs.iter().map(|l| format!("[{}]", l)).collect();
+                    filter_lines.push(format!("{}concat=n={}:v=1:a=0[bgcat]", ins, bg_inputs.len()));
+                    "bgcat".to_string()
+                } else {
+                    bg_inputs[0].clone()
+                };
         
-        filter_lines.push(format!("[{}]setpts=PTS-STARTPTS,setsar=1[bgtrim]", prev));
-        let mut bg_label = "bgtrim".to_string();
+                filter_lines.push(format!("[{}]setpts=PTS-STARTPTS,setsar=1[bgtrim]", prev));
+                let mut bg_label = "bgtrim".to_string();
         
-        if avail_bg_after + 1e-6 < duration_s {
-            let remain = duration_s - avail_bg_after;
-            let color_pad_idx = current_idx;
-            cmd.extend_from_slice(&[
-                "-f".to_string(), "lavfi".to_string(),
-                "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, remain),
-            ]);
-            current_idx += 1;
-            filter_lines.push(format!("[{}:v]setsar=1[colorpad]", color_pad_idx));
-            filter_lines.push(format!("[bgtrim][colorpad]concat=n=2:v=1:a=0[bg]"));
-            bg_label = "bg".to_string();
-        }
+                if avail_bg_after + 1e-6 < duration_s {
+                    let remain = duration_s - avail_bg_after;
+                    let color_pad_idx = current_idx;
+                    cmd.extend_from_slice(&[
+                        "-f".to_string(), "lavfi".to_string(),
+                        "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, remain),
+                    ]);
+                    current_idx += 1;
+                    filter_lines.push(format!("[{}:v]setsar=1[colorpad]", color_pad_idx));
+                    filter_lines.push(format!("[bgtrim][colorpad]concat=n=2:v=1:a=0[bg]"));
+                    bg_label = "bg".to_string();
+                }
         
-        bg_label
-    };
-    
-    // Superposition de l'overlay (avec alpha) sur le fond
-    filter_lines.push(format!("[{}]setsar=1[bg_normalized]", bg_label));
-    filter_lines.push(format!("[bg_normalized][overlay]overlay=shortest=1:x=0:y=0,format=yuv420p[vout]"));
+                bg_label
+            };
     
-    // Audio: concat, skip start_s, clamp à duration_s
-    if have_audio {
-        let a = audio_paths.len();
-        if a == 1 {
-            let a0 = format!("{}:a", audio_start_idx);
-            filter_lines.push(format!("[{}]aresample=48000[aa0]", a0));
-            filter_lines.push(format!("[aa0]atrim=start={:.6},asetpts=PTS-STARTPTS,atrim=end={:.6}[aout]", start_s, duration_s));
-        } else {
-            for j in 0..a {
-                let idx = audio_start_idx + j;
-                filter_lines.push(format!("[{}:a]aresample=48000[aa{}]", idx, j));
-            }
-            let mut ins = String::new();
-            for j in 0..a {
-                ins.push_str(&format!("[aa{}]", j));
-            }
-            filter_lines.push(format!("{}concat=n={}:v=0:a=1[aacat]", ins, a));
-            filter_lines.push(format!("[aacat]atrim=start={:.6},asetpts=PTS-STARTPTS,atrim=end={:.6}[aout]", start_s, duration_s));
+            // Superposition de l'overlay (avec alpha) sur le fond
+            filter_lines.push(format!("[{}]setsar=1[bg_normalized]", bg_label));
+            vout_line = Some(filter_lines.len());
+            filter_lines.push("[bg_normalized][overlay]overlay=shortest=1:x=0:y=0,format=yuv420p[vout]".to_string());
         }
     }
     
-    let filter_complex = filter_lines.join(";");
+    // Audio: couches mixées, silence entre les clips
+    if have_audio {
+        let normalize = loudness.map(|(options, measured)| options.normalize_filter(&measured));
+        filter_lines.extend(audio::mix_filter(audio_layers, &audio_placements, audio_start_idx, start_s, duration_s, normalize.as_deref()));
+    }
     
-    // Écrit le filtergraph dans un fichier temporaire
     let tmp_dir = if let Some(cwd) = imgs_cwd {
         PathBuf::from(cwd)
     } else {
@@ -856,54 +1549,187 @@ fn build_and_run_ffmpeg_filter_complex(
     };
     fs::create_dir_all(&tmp_dir).ok();
     
-    let fg_hash = format!("{:x}", md5::compute(filter_complex.as_bytes()));
-    let fg_path = tmp_dir.join(format!("filter-{}.ffgraph", &fg_hash[..8]));
-    
-    fs::write(&fg_path, &filter_complex)?;
-    println!("[ffmpeg] filter_complex_script -> {:?}", fg_path);
-    
-    let fg_name = if imgs_cwd.is_some() {
-        fg_path.file_name().unwrap().to_string_lossy().to_string()
-    } else {
-        fg_path.to_string_lossy().to_string()
+    // Entrées sous-titres soft (après toutes les autres entrées)
+    let soft_subs = match soft_subtitles {
+        Some(options) => subtitles::prepare_soft_subtitles(
+            options,
+            export_id,
+            out_path,
+            start_time_ms as i64,
+            Some((duration_s * 1000.0).round() as i64),
+            (w as u32, h as u32),
+            &tmp_dir,
+        )?,
+        None => None,
     };
-    
-    cmd.extend_from_slice(&["-filter_complex_script".to_string(), fg_name]);
-    
-    // Mapping
-    cmd.extend_from_slice(&["-map".to_string(), "[vout]".to_string()]);
-    if have_audio {
-        cmd.extend_from_slice(&["-map".to_string(), "[aout]".to_string()]);
+    let subs_start_idx = current_idx;
+    if let Some(ref subs) = soft_subs {
+        cmd.extend(subs.input_args());
     }
     
-    // Codec vidéo + audio
-    cmd.extend_from_slice(&["-r".to_string(), fps.to_string(), "-c:v".to_string(), vcodec]);
-    if let Some(Some(preset)) = vextra.get("preset") {
-        cmd.extend_from_slice(&["-preset".to_string(), preset.clone()]);
-    }
-    cmd.extend(vparams);
-    
-    if have_audio {
-        cmd.extend_from_slice(&["-c:a".to_string(), "aac".to_string(), "-b:a".to_string(), "192k".to_string()]);
-    }
-    
-    // Assure la durée exacte
-    cmd.extend_from_slice(&["-t".to_string(), format!("{:.6}", duration_s)]);
-    
-    // Faststart pour formats MP4/MOV
     let ext = Path::new(out_path)
         .extension()
         .and_then(|s| s.to_str())
         .unwrap_or("")
         .to_lowercase();
     
-    if matches!(ext.as_str(), "mp4" | "mov" | "m4v") {
-        cmd.extend_from_slice(&["-movflags".to_string(), "+faststart".to_string()]);
+    // Encodage proprement dit : seule étape relancée si l'encodeur matériel tombe en panne
+    let encode = |vcodec: &str, vparams: &[String], vextra: &HashMap<String, Option<String>>| -> Result<String, ExportError> {
+        let mut filter_lines = filter_lines.clone();
+        if let (Some(i), Some(Some(upload))) = (vout_line, vextra.get("hwupload")) {
+            filter_lines[i] = filter_lines[i].replace("[vout]", &format!(",{}[vout]", upload));
+        }
+        let filter_complex = filter_lines.join(";");
+        
+        // Écrit le filtergraph dans un fichier temporaire
+        let fg_hash = format!("{:x}", md5::compute(filter_complex.as_bytes()));
+        let fg_path = tmp_dir.join(format!("filter-{}.ffgraph", &fg_hash[..8]));
+        
+        fs::write(&fg_path, &filter_complex)?;
+        token.track_temp_file(&fg_path);
+        println!("[ffmpeg] filter_complex_script -> {:?}", fg_path);
+        
+        let fg_name = if imgs_cwd.is_some() {
+            fg_path.file_name().unwrap().to_string_lossy().to_string()
+        } else {
+            fg_path.to_string_lossy().to_string()
+        };
+        
+        let mut cmd = cmd.clone();
+        cmd.extend_from_slice(&["-filter_complex_script".to_string(), fg_name]);
+        
+        // Mapping
+        if !audio_only {
+            cmd.extend_from_slice(&["-map".to_string(), "[vout]".to_string()]);
+        }
+        if have_audio {
+            cmd.extend_from_slice(&["-map".to_string(), "[aout]".to_string()]);
+        }
+        if let Some(ref subs) = soft_subs {
+            cmd.extend(subs.output_args(subs_start_idx));
+        }
+        
+        // Codec vidéo + audio
+        if let Some(format) = overlay_format {
+            cmd.extend_from_slice(&["-r".to_string(), fps.to_string()]);
+            cmd.extend(format.codec_args());
+        } else if !audio_only {
+            cmd.extend_from_slice(&["-r".to_string(), fps.to_string()]);
+            cmd.extend_from_slice(&["-c:v".to_string(), vcodec.to_string()]);
+            if let Some(Some(preset)) = vextra.get("preset") {
+                cmd.extend_from_slice(&["-preset".to_string(), preset.clone()]);
+            }
+            cmd.extend_from_slice(vparams);
+            
+            // Tag hvc1 pour que le HEVC soit lisible par QuickTime/Safari
+            if output_format.codec == VideoCodec::Hevc && matches!(ext.as_str(), "mp4" | "mov" | "m4v") {
+                cmd.extend_from_slice(&["-tag:v".to_string(), "hvc1".to_string()]);
+            }
+        }
+        
+        if have_audio {
+            let acodec = audio_codec_for_extension(&ext);
+            let audio_kbps = profile.map(|p| p.audio_bitrate_kbps).unwrap_or(192);
+            cmd.extend_from_slice(&["-c:a".to_string(), acodec.to_string(), "-b:a".to_string(), format!("{}k", audio_kbps)]);
+        }
+        
+        // Assure la durée exacte
+        cmd.extend_from_slice(&["-t".to_string(), format!("{:.6}", duration_s)]);
+        
+        // Faststart pour formats MP4/MOV
+        if matches!(ext.as_str(), "mp4" | "mov" | "m4v") {
+            cmd.extend_from_slice(&["-movflags".to_string(), "+faststart".to_string()]);
+        }
+        
+        match profile.filter(|p| p.needs_two_runs(vcodec)) {
+            Some(_) => {
+                // Double passage : la 1re passe n'écrit que les statistiques
+                let passlog = tmp_dir.join(format!("passlog-{}-{}", export_id, chunk_index.unwrap_or(0)));
+                
+                let mut pass1 = cmd.clone();
+                pass1.extend(EncodingProfile::pass_args(vcodec, 1, &passlog));
+                pass1.extend_from_slice(&["-f".to_string(), "null".to_string(), "-".to_string()]);
+                
+                let mut pass2 = cmd.clone();
+                pass2.extend(EncodingProfile::pass_args(vcodec, 2, &passlog));
+                pass2.push(out_path.to_string());
+                
+                let result = run_ffmpeg_with_progress(&pass1, imgs_cwd, export_id, duration_s, (0, 2), ExportPhase::Encoding, chunk_index, None, progress_tracker, &app_handle)
+                    .and_then(|_| run_ffmpeg_with_progress(&pass2, imgs_cwd, export_id, duration_s, (1, 2), ExportPhase::Encoding, chunk_index, None, progress_tracker, &app_handle));
+                
+                // Supprimer les fichiers de statistiques (.log, .log.mbtree, ...)
+                let prefix = passlog.file_name().unwrap_or_default().to_string_lossy().to_string();
+                if let Ok(entries) = fs::read_dir(&tmp_dir) {
+                    for entry in entries.flatten() {
+                        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                            let _ = fs::remove_file(entry.path());
+                        }
+                    }
+                }
+                result
+            }
+            None => {
+                cmd.push(out_path.to_string());
+                run_ffmpeg_with_progress(&cmd, imgs_cwd, export_id, duration_s, (0, 1), ExportPhase::Encoding, chunk_index, None, progress_tracker, &app_handle)
+            }
+        }
+    };
+    
+    let mut result = encode(&vcodec, &vparams, &vextra);
+    
+    // Encodeur matériel en panne en cours d'export : on relance l'encodage une fois en logiciel,
+    // sans refaire le prétraitement des fonds ni la mesure du volume
+    if let Err(ExportError::EncoderFailed { ref stderr, .. }) = result {
+        if encoder_backend::is_encoder_failure(&vcodec, stderr) {
+            let (fallback, mut fallback_params, mut fallback_extra) = software_codec(output_format.codec, two_pass);
+            println!("[codec] ⚠️ {} a échoué pendant l'export, nouvel essai avec {}", vcodec, fallback);
+            
+            let mut warning_data = serde_json::json!({
+                "export_id": export_id,
+                "message": format!("L'encodeur {} a échoué, export relancé avec {}", vcodec, fallback),
+                "encoder": vcodec,
+                "fallback_encoder": fallback
+            });
+            if let Some(chunk_idx) = chunk_index {
+                warning_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+            }
+            let _ = app_handle.emit("export-warning", warning_data);
+            
+            if let Some(profile) = profile {
+                apply_encoding_profile(profile, &fallback, fps, &mut fallback_params, &mut fallback_extra);
+            }
+            result = encode(&fallback, &fallback_params, &fallback_extra);
+        }
     }
     
-    // Fichier de sortie
-    cmd.push(out_path.to_string());
+    if let Some(ref subs) = soft_subs {
+        subs.cleanup();
+    }
+    
+    if let Err(ref err @ ExportError::EncoderFailed { .. }) = result {
+        emit_export_error(&app_handle, export_id, chunk_index, err);
+    }
     
+    result.map(|_| loudness.map(|(_, measured)| measured))
+}
+
+/// Lance FFmpeg en relayant sa progression, et renvoie sa sortie stderr.
+///
+/// `pass` = (index de la passe, nombre de passes) : la progression d'un encodage en deux
+/// passes est répartie sur les deux lancements.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_ffmpeg_with_progress(
+    cmd: &[String],
+    imgs_cwd: Option<&str>,
+    export_id: &str,
+    duration_s: f64,
+    pass: (usize, usize),
+    phase: ExportPhase,
+    chunk_index: Option<i32>,
+    source_file: Option<&str>,
+    progress_tracker: Option<&ExportProgressTracker>,
+    app_handle: &tauri::AppHandle,
+) -> Result<String, ExportError> {
     println!("[ffmpeg] Commande:");
     let preview = if cmd.len() > 14 {
         format!("{} ...", cmd[..14].join(" "))
@@ -924,96 +1750,121 @@ fn build_and_run_ffmpeg_filter_complex(
         command.current_dir(cwd);
     }
     
-    let mut child = command.spawn()?;
+    // Ne pas lancer de nouveau processus pour un export déjà annulé
+    let token = export_token(export_id);
+    if token.is_cancelled() {
+        return Err(ExportError::Cancelled { export_id: export_id.to_string() });
+    }
+    
+    let child = command.spawn()?;
     
-    // Enregistrer le processus dans les exports actifs
+    // Enregistrer le processus dans le jeton de l'export
     let process_ref = Arc::new(Mutex::new(Some(child)));
-    {
-        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
-        active_exports.insert(export_id.to_string(), process_ref.clone());
-    }
+    token.register(&process_ref);
     
     let stderr = {
-        let mut child_guard = process_ref.lock().map_err(|_| "Failed to lock child process")?;
+        let mut child_guard = process_ref.lock().map_err(|_| ExportError::io("Failed to lock child process"))?;
         if let Some(ref mut child) = child_guard.as_mut() {
-            child.stderr.take().ok_or("Failed to capture stderr")?
+            child.stderr.take().ok_or_else(|| ExportError::io("Failed to capture stderr"))?
         } else {
-            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "Process was cancelled")));
+            token.unregister(&process_ref);
+            return Err(ExportError::Cancelled { export_id: export_id.to_string() });
         }
     };
     
     // Lire la sortie stderr pour capturer la progression
     let reader = BufReader::new(stderr);
     let mut stderr_content = String::new();
+    let mut sample = FfmpegProgress::default();
+    let started = Instant::now();
     
     for line in reader.lines() {
         if let Ok(line) = line {
             println!("[ffmpeg] {}", line); // Debug: afficher toutes les lignes
             
-            // Sauvegarder toutes les lignes stderr pour le debugging
-            stderr_content.push_str(&line);
-            stderr_content.push('\n');
+            // Sauvegarder toutes les lignes stderr pour le debugging
+            stderr_content.push_str(&line);
+            stderr_content.push('\n');
+            
+            // Les blocs `-progress pipe:2` se terminent par une ligne "progress=continue|end"
+            if !sample.apply_line(&line) {
+                continue;
+            }
+            
+            let (pass_idx, passes) = pass;
+            let pass_time_s = sample.out_time_s.unwrap_or(0.0).clamp(0.0, duration_s);
+            let current_time_s = (pass_idx as f64 * duration_s + pass_time_s) / passes as f64;
+            let progress = if duration_s > 0.0 {
+                (current_time_s / duration_s * 100.0).min(100.0)
+            } else {
+                0.0
+            };
+            
+            // Temps média restant sur toutes les passes, divisé par la vitesse d'encodage
+            let remaining_s = (duration_s - current_time_s).max(0.0) * passes as f64;
+            let eta_s = estimate_eta(remaining_s, sample.speed, current_time_s * passes as f64, started);
             
-            // Chercher les lignes de progression FFmpeg qui contiennent "time=" ou "out_time_ms="
-            if line.contains("time=") || line.contains("out_time_ms=") {
-                if let Some(time_str) = extract_time_from_ffmpeg_line(&line) {
-                    let current_time_s = parse_ffmpeg_time(&time_str);
-                    let progress = if duration_s > 0.0 {
-                        (current_time_s / duration_s * 100.0).min(100.0)
-                    } else {
-                        0.0
-                    };
-                    
-                    println!("[progress] {}% ({:.1}s / {:.1}s)", progress.round(), current_time_s, duration_s);
-                    
-                    // Préparer les données de progression
-                    let mut progress_data = serde_json::json!({
-                        "export_id": export_id,
-                        "progress": progress,
-                        "current_time": current_time_s,
-                        "total_time": duration_s
-                    });
-                    
-                    // Ajouter chunk_index si fourni
-                    if let Some(chunk_idx) = chunk_index {
-                        progress_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
-                    }
-                    
-                    // Émettre l'événement de progression vers le frontend
-                    let _ = app_handle.emit("export-progress", progress_data);
-                }
+            println!(
+                "[progress] {}% ({:.1}s / {:.1}s, {:.2}x, ETA {:.0}s)",
+                progress.round(), current_time_s, duration_s, sample.speed.unwrap_or(0.0), eta_s.unwrap_or(-1.0)
+            );
+            
+            // Progression agrégée quand plusieurs chunks tournent en parallèle
+            if let Some(tracker) = progress_tracker.filter(|_| phase == ExportPhase::Encoding) {
+                tracker.report(chunk_index.unwrap_or(0), current_time_s, duration_s, &sample);
+                continue;
             }
-        }
-    }
-    
-    // Attendre la fin du processus
-    let status = {
-        let mut child_guard = process_ref.lock().map_err(|_| "Failed to lock child process")?;
-        if let Some(mut child) = child_guard.take() {
-            child.wait()?
-        } else {
-            // Le processus a été annulé
-            let error_msg = format!("Export {} was cancelled", export_id);
-            let mut error_data = serde_json::json!({
+            
+            // Préparer les données de progression
+            let mut progress_data = serde_json::json!({
                 "export_id": export_id,
-                "error": error_msg
+                "phase": phase,
+                "progress": progress,
+                "current_time": current_time_s,
+                "total_time": duration_s,
+                "eta_s": eta_s,
+                "frame": sample.frame,
+                "fps": sample.fps,
+                "bitrate_kbps": sample.bitrate_kbps,
+                "total_size": sample.total_size,
+                "speed": sample.speed,
+                "paused": token.is_paused()
             });
             
             // Ajouter chunk_index si fourni
             if let Some(chunk_idx) = chunk_index {
-                error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+                progress_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+            }
+            
+            // Fichier en cours de traitement (prétraitement des fonds, concaténation)
+            if let Some(source) = source_file {
+                progress_data["source_file"] = serde_json::Value::String(source.to_string());
+            }
+            
+            // Hors encodage, l'avancement ne concerne que le fichier traité : il est envoyé à part
+            // (`progress` à null) pour ne pas se mélanger à la progression de l'export
+            if phase != ExportPhase::Encoding {
+                progress_data["phase_progress"] = progress_data["progress"].take();
             }
             
-            let _ = app_handle.emit("export-error", error_data);
-            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Interrupted, error_msg)));
+            // Émettre l'événement de progression vers le frontend
+            let _ = app_handle.emit("export-progress", progress_data);
         }
+    }
+    
+    // Attendre la fin du processus
+    let status = {
+        let mut child_guard = process_ref.lock().map_err(|_| ExportError::io("Failed to lock child process"))?;
+        child_guard.take().map(|mut child| child.wait()).transpose()
     };
+    token.unregister(&process_ref);
     
-    // Nettoyer les exports actifs
-    {
-        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
-        active_exports.remove(export_id);
-    }
+    let Some(status) = status? else {
+        // Le processus a été annulé
+        let err = ExportError::Cancelled { export_id: export_id.to_string() };
+        emit_export_error(app_handle, export_id, chunk_index, &err);
+        return Err(err);
+    };
     
     if !status.success() {
         // Créer un fichier de log avec la date d'aujourd'hui
@@ -1040,9 +1891,9 @@ fn build_and_run_ffmpeg_filter_complex(
             status.code(),
             cmd.join(" "),
             if stderr_content.is_empty() {
-                "No stderr output captured".to_string()
+                "No stderr output captured"
             } else {
-                stderr_content
+                &stderr_content
             }
         );
         
@@ -1053,27 +1904,372 @@ fn build_and_run_ffmpeg_filter_complex(
             println!("FFmpeg error details saved to: {}", log_filename);
         }
         
-        let error_msg = format!(
-            "ffmpeg failed during video exportation (exit code: {:?})\n\nSee the log file: {}\n\nLog details:\n{}", 
-            status.code(), 
-            log_filename,
-            log_content
-        );
-        let mut error_data = serde_json::json!({
-            "export_id": export_id,
-            "error": error_msg
+        let err = ExportError::EncoderFailed {
+            exit_code: status.code(),
+            log_path: log_filename,
+            stderr: stderr_content,
+        };
+        return Err(err);
+    }
+    
+    Ok(stderr_content)
+}
+
+/// Paramètres d'export d'un chunk, identiques à ceux de `export_video`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExportChunkSpec {
+    pub chunk_index: Option<i32>,
+    pub imgs_folder: String,
+    pub final_file_path: String,
+    pub fps: i32,
+    pub fade_duration: i32,
+    pub start_time: i32,
+    pub duration: Option<i32>,
+    pub audios: Option<Vec<AudioSource>>,
+    pub videos: Option<Vec<BackgroundSource>>,
+    /// Couches audio mixées avec `audios` (ambiance, nasheed)
+    #[serde(default)]
+    pub audio_layers: Option<Vec<AudioLayer>>,
+    #[serde(default)]
+    pub soft_subtitles: Option<SoftSubtitleOptions>,
+    #[serde(default)]
+    pub overlay_format: Option<OverlayFormat>,
+    #[serde(default)]
+    pub output_format: Option<OutputFormat>,
+    #[serde(default)]
+    pub encoding_profile: Option<EncodingProfileChoice>,
+    /// Projet exporté, pour rattacher les fichiers du cache de prétraitement au projet
+    #[serde(default)]
+    pub project_id: Option<String>,
+}
+
+/// Export des sous-titres seuls, sans fond, avec canal alpha (pour compositing dans DaVinci/Premiere)
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayFormat {
+    /// ProRes 4444 dans un .mov
+    Prores4444,
+    /// VP9 avec alpha dans un .webm
+    Vp9Alpha,
+    /// Séquence de PNG RGBA dans un dossier
+    PngSequence,
+}
+
+impl OverlayFormat {
+    fn pix_fmt(self) -> &'static str {
+        match self {
+            OverlayFormat::Prores4444 => "yuva444p10le",
+            OverlayFormat::Vp9Alpha => "yuva420p",
+            OverlayFormat::PngSequence => "rgba",
+        }
+    }
+
+    fn codec_args(self) -> Vec<String> {
+        let args: &[&str] = match self {
+            OverlayFormat::Prores4444 => &["-c:v", "prores_ks", "-profile:v", "4", "-alpha_bits", "16", "-vendor", "apl0"],
+            // auto-alt-ref doit être désactivé pour que libvpx encode l'alpha
+            OverlayFormat::Vp9Alpha => &["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "30", "-row-mt", "1", "-auto-alt-ref", "0"],
+            OverlayFormat::PngSequence => &["-c:v", "png"],
+        };
+        let mut args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        args.extend(["-pix_fmt".to_string(), self.pix_fmt().to_string()]);
+        args
+    }
+
+    fn supports_audio(self) -> bool {
+        !matches!(self, OverlayFormat::PngSequence)
+    }
+
+    /// Chemin réellement écrit : extension imposée, ou dossier pour la séquence PNG
+    fn output_path(self, final_file_path: &str) -> PathBuf {
+        let path = Path::new(final_file_path);
+        match self {
+            OverlayFormat::Prores4444 => path.with_extension("mov"),
+            OverlayFormat::Vp9Alpha => path.with_extension("webm"),
+            OverlayFormat::PngSequence => path.with_extension(""),
+        }
+    }
+}
+
+/// Agrège la progression de plusieurs chunks encodés en parallèle en un seul flux `export-progress`
+pub(crate) struct ExportProgressTracker {
+    export_id: String,
+    chunks: Mutex<HashMap<i32, ChunkProgress>>,
+    started: Instant,
+    app_handle: tauri::AppHandle,
+}
+
+#[derive(Default)]
+struct ChunkProgress {
+    /// Temps encodé et durée totale, en secondes
+    current_s: f64,
+    total_s: f64,
+    speed: Option<f64>,
+    fps: Option<f64>,
+}
+
+impl ExportProgressTracker {
+    pub(crate) fn new(export_id: &str, specs: &[ExportChunkSpec], app_handle: tauri::AppHandle) -> Self {
+        let chunks = specs
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| {
+                let total_s = spec.duration.unwrap_or(0) as f64 / 1000.0;
+                (spec.chunk_index.unwrap_or(i as i32), ChunkProgress { total_s, ..Default::default() })
+            })
+            .collect();
+
+        ExportProgressTracker {
+            export_id: export_id.to_string(),
+            chunks: Mutex::new(chunks),
+            started: Instant::now(),
+            app_handle,
+        }
+    }
+
+    fn report(&self, chunk_index: i32, current_time_s: f64, total_time_s: f64, sample: &FfmpegProgress) {
+        let Ok(mut chunks) = self.chunks.lock() else {
+            return;
+        };
+        chunks.insert(chunk_index, ChunkProgress {
+            current_s: current_time_s.min(total_time_s),
+            total_s: total_time_s,
+            speed: sample.speed,
+            fps: sample.fps,
         });
-        
-        // Ajouter chunk_index si fourni
-        if let Some(chunk_idx) = chunk_index {
-            error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+
+        let current: f64 = chunks.values().map(|c| c.current_s).sum();
+        let total: f64 = chunks.values().map(|c| c.total_s).sum();
+        let is_done = |c: &ChunkProgress| c.total_s > 0.0 && c.current_s >= c.total_s;
+        let done = chunks.values().filter(|c| is_done(c)).count();
+        let progress = if total > 0.0 { (current / total * 100.0).min(100.0) } else { 0.0 };
+
+        // Les chunks en cours avancent en même temps : leurs vitesses s'additionnent
+        let running = || chunks.values().filter(|c| !is_done(c) && c.current_s > 0.0);
+        let speed: f64 = running().filter_map(|c| c.speed).sum();
+        let fps: f64 = running().filter_map(|c| c.fps).sum();
+        let eta_s = estimate_eta((total - current).max(0.0), Some(speed), current, self.started);
+
+        let _ = self.app_handle.emit("export-progress", serde_json::json!({
+            "export_id": self.export_id,
+            "phase": ExportPhase::Encoding,
+            "progress": progress,
+            "current_time": current,
+            "total_time": total,
+            "eta_s": eta_s,
+            "fps": fps,
+            "speed": speed,
+            "chunks_done": done,
+            "chunks_total": chunks.len(),
+            "paused": is_export_paused(&self.export_id)
+        }));
+    }
+
+    fn complete(&self, chunk_index: i32) {
+        let total = self
+            .chunks
+            .lock()
+            .ok()
+            .and_then(|chunks| chunks.get(&chunk_index).map(|c| c.total_s))
+            .unwrap_or(0.0);
+        self.report(chunk_index, total, total, &FfmpegProgress::default());
+    }
+}
+
+/// Nombre de ffmpeg lancés en parallèle par défaut (chaque ffmpeg est déjà multi-thread)
+fn default_export_workers() -> usize {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    (cores / 4).clamp(1, 4)
+}
+
+/// Couches audio d'un export : la récitation (`audios`) en premier, sans ducking
+fn merge_audio_layers(audios: Option<Vec<AudioSource>>, audio_layers: Option<Vec<AudioLayer>>) -> Vec<AudioLayer> {
+    let mut layers: Vec<AudioLayer> = audios.map(AudioLayer::recitation).into_iter().collect();
+    layers.extend(audio_layers.unwrap_or_default());
+    layers
+}
+
+/// Mesure le volume une seule fois sur toute la plage des chunks, pour qu'ils reçoivent tous le même gain.
+///
+/// Renvoie vrai si une mesure a été ajoutée aux chunks. Sans durée connue pour chaque chunk,
+/// chacun est mesuré séparément.
+pub(crate) fn measure_shared_loudness(
+    export_id: &str,
+    specs: &mut [ExportChunkSpec],
+    app: &tauri::AppHandle,
+) -> Result<bool, ExportError> {
+    let Some(first) = specs.first() else {
+        return Ok(false);
+    };
+    let Some(options) = first.output_format.as_ref().and_then(|f| f.loudness.clone()) else {
+        return Ok(false);
+    };
+    if options.measured.is_some() {
+        return Ok(false);
+    }
+    if specs.iter().any(|s| s.duration.is_none()) {
+        println!("[loudness] Durée de chunk inconnue, mesure séparée pour chaque chunk");
+        return Ok(false);
+    }
+
+    let start_ms = specs.iter().map(|s| s.start_time).min().unwrap_or(0);
+    let end_ms = specs.iter().map(|s| s.start_time + s.duration.unwrap_or(0)).max().unwrap_or(0);
+    let layers = merge_audio_layers(first.audios.clone(), first.audio_layers.clone());
+    let start_s = start_ms.max(0) as f64 / 1000.0;
+    let duration_s = (end_ms - start_ms).max(0) as f64 / 1000.0;
+
+    let durations = audio::source_durations(&layers, export_id);
+    let Some(measured) = loudness::measure(export_id, &layers, &durations, &options, start_s, duration_s, None, app)? else {
+        return Ok(false);
+    };
+    for spec in specs.iter_mut() {
+        if let Some(options) = spec.output_format.as_mut().and_then(|f| f.loudness.as_mut()) {
+            options.measured = Some(measured);
         }
-        
-        let _ = app_handle.emit("export-error", error_data);
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, error_msg)));
     }
-    
-    Ok(())
+    Ok(true)
+}
+
+/// Une taille cible vaut pour la vidéo finale : elle est répartie entre les chunks au prorata de leur durée.
+pub(crate) fn split_target_size(specs: &mut [ExportChunkSpec]) {
+    if specs.iter().any(|s| s.duration.is_none()) {
+        return;
+    }
+    let total_ms: i64 = specs.iter().map(|s| s.duration.unwrap_or(0) as i64).sum();
+    if total_ms <= 0 {
+        return;
+    }
+
+    for spec in specs.iter_mut() {
+        let Some(mut profile) = spec.encoding_profile.as_ref().map(|p| p.resolve()) else {
+            continue;
+        };
+        if let RateControl::TargetSize { max_mb } = profile.rate_control {
+            let share = spec.duration.unwrap_or(0) as f64 / total_ms as f64;
+            profile.rate_control = RateControl::TargetSize { max_mb: max_mb * share };
+            spec.encoding_profile = Some(EncodingProfileChoice::Custom(profile));
+        }
+    }
+}
+
+/// Une couche en boucle sans `end_ms` joue jusqu'à la fin de la plage exportée. Pour un export en
+/// chunks, cette fin est celle de l'export entier (pas celle du chunk), sinon le fondu de sortie
+/// serait rejoué à la fin de chaque chunk.
+pub(crate) fn pin_loop_layer_ends(specs: &mut [ExportChunkSpec]) {
+    let ends: Option<Vec<i32>> = specs.iter().map(|s| s.duration.map(|d| s.start_time + d)).collect();
+    let Some(end_ms) = ends.and_then(|ends| ends.into_iter().max()) else {
+        println!("[audio] Durée de chunk inconnue, fin des couches en boucle non fixée");
+        return;
+    };
+    for spec in specs.iter_mut() {
+        for layer in spec.audio_layers.iter_mut().flatten() {
+            if layer.loop_until_end && layer.end_ms.is_none() {
+                layer.end_ms = Some(end_ms);
+            }
+        }
+    }
+}
+
+/// Encode tous les chunks avec `workers` processus ffmpeg en parallèle.
+///
+/// `on_chunk_done` est appelé après chaque chunk (dans l'ordre de fin, pas l'ordre des chunks).
+/// Après la première erreur, plus aucun nouveau chunk n'est lancé.
+pub(crate) fn export_chunks_parallel(
+    export_id: &str,
+    specs: Vec<ExportChunkSpec>,
+    workers: Option<usize>,
+    app: &tauri::AppHandle,
+    on_chunk_done: &(dyn Fn(usize, &Result<String, ExportError>) + Sync),
+) -> Result<Vec<String>, ExportError> {
+    if specs.len() > 1 && specs.iter().any(|s| s.overlay_format == Some(OverlayFormat::PngSequence)) {
+        return Err(ExportError::invalid_input("La séquence PNG ne peut pas être exportée en plusieurs chunks"));
+    }
+    
+    let workers = workers.unwrap_or_else(default_export_workers).clamp(1, specs.len().max(1));
+    println!("[export_chunks] {} chunk(s), {} worker(s)", specs.len(), workers);
+
+    let token = export_token(export_id);
+    let tracker = ExportProgressTracker::new(export_id, &specs, app.clone());
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<String, ExportError>>>> = Mutex::new((0..specs.len()).map(|_| None).collect());
+    let first_error: Mutex<Option<ExportError>> = Mutex::new(None);
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if token.is_cancelled() || first_error.lock().map(|e| e.is_some()).unwrap_or(true) {
+                    break;
+                }
+                let i = next.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let Some(spec) = specs.get(i).cloned() else {
+                    break;
+                };
+                let chunk_index = spec.chunk_index.unwrap_or(i as i32);
+
+                let result = export_chunk_blocking(export_id, spec, Some(&tracker), app);
+                if result.is_ok() {
+                    tracker.complete(chunk_index);
+                }
+                on_chunk_done(i, &result);
+
+                match result {
+                    Ok(path) => {
+                        if let Ok(mut results) = results.lock() {
+                            results[i] = Some(Ok(path));
+                        }
+                    }
+                    Err(e) => {
+                        println!("[export_chunks] Chunk {} en échec: {}", chunk_index, e);
+                        if let Ok(mut first) = first_error.lock() {
+                            first.get_or_insert(e);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let cancelled = token.is_cancelled();
+
+    if let Some(e) = first_error.into_inner().ok().flatten() {
+        return Err(e);
+    }
+    if cancelled {
+        return Err(ExportError::Cancelled { export_id: export_id.to_string() });
+    }
+
+    results
+        .into_inner()
+        .map_err(|_| ExportError::io("Failed to lock chunk results"))?
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| Err(ExportError::Cancelled { export_id: export_id.to_string() })))
+        .collect()
+}
+
+/// Encode plusieurs chunks en parallèle et renvoie les fichiers produits dans l'ordre des chunks
+#[tauri::command]
+pub async fn export_chunks(
+    export_id: String,
+    chunks: Vec<ExportChunkSpec>,
+    workers: Option<usize>,
+    app: tauri::AppHandle,
+) -> Result<Vec<String>, ExportError> {
+    if chunks.is_empty() {
+        return Err(ExportError::invalid_input("Aucun chunk fourni"));
+    }
+
+    let mut chunks = chunks;
+    split_target_size(&mut chunks);
+    pin_loop_layer_ends(&mut chunks);
+
+    task::spawn_blocking(move || {
+        run_cancellable(&export_id, &app, || {
+            measure_shared_loudness(&export_id, &mut chunks, &app)?;
+            export_chunks_parallel(&export_id, chunks, workers, &app, &|_, _| {})
+        })
+    })
+        .await
+        .map_err(|e| ExportError::io(format!("Erreur tâche: {}", e)))?
 }
 
 #[tauri::command]
@@ -1085,11 +2281,70 @@ pub async fn export_video(
     fade_duration: i32,
     start_time: i32,
     duration: Option<i32>,
-    audios: Option<Vec<String>>,
-    videos: Option<Vec<String>>,
+    audios: Option<Vec<AudioSource>>,
+    videos: Option<Vec<BackgroundSource>>,
+    audio_layers: Option<Vec<AudioLayer>>,
     chunk_index: Option<i32>,
+    soft_subtitles: Option<SoftSubtitleOptions>,
+    overlay_format: Option<OverlayFormat>,
+    output_format: Option<OutputFormat>,
+    encoding_profile: Option<EncodingProfileChoice>,
+    project_id: Option<String>,
     app: tauri::AppHandle,
-) -> Result<String, String> {
+) -> Result<String, ExportError> {
+    let spec = ExportChunkSpec {
+        chunk_index,
+        imgs_folder,
+        final_file_path,
+        fps,
+        fade_duration,
+        start_time,
+        duration,
+        audios,
+        videos,
+        audio_layers,
+        soft_subtitles,
+        overlay_format,
+        output_format,
+        encoding_profile,
+        project_id,
+    };
+
+    task::spawn_blocking(move || run_cancellable(&export_id, &app, || export_chunk_blocking(&export_id, spec, None, &app)))
+        .await
+        .map_err(|e| ExportError::io(format!("Erreur tâche: {}", e)))?
+}
+
+/// Exporte un chunk (ou une vidéo complète) de façon bloquante.
+///
+/// Si `progress_tracker` est fourni, la progression est agrégée avec celle des autres
+/// chunks au lieu d'être émise telle quelle.
+pub(crate) fn export_chunk_blocking(
+    export_id: &str,
+    spec: ExportChunkSpec,
+    progress_tracker: Option<&ExportProgressTracker>,
+    app: &tauri::AppHandle,
+) -> Result<String, ExportError> {
+    let ExportChunkSpec {
+        chunk_index,
+        imgs_folder,
+        final_file_path,
+        fps,
+        fade_duration,
+        start_time,
+        duration,
+        audios,
+        videos,
+        audio_layers,
+        soft_subtitles,
+        overlay_format,
+        output_format,
+        encoding_profile,
+        project_id,
+    } = spec;
+    let encoding_profile = encoding_profile.map(|p| p.resolve());
+    let output_format = output_format.unwrap_or_default();
+    output_format.validate()?;
     let t0 = Instant::now();
     
     // Logs init
@@ -1104,6 +2359,9 @@ pub async fn export_video(
     } else {
         println!("[audio] aucun fichier audio fourni");
     }
+    if let Some(ref layers) = audio_layers {
+        println!("[audio] {} couche(s) audio supplémentaire(s)", layers.len());
+    }
     
     if let Some(ref videos) = videos {
         println!("[video] {} fichier(s) vidéo fourni(s)", videos.len());
@@ -1116,7 +2374,7 @@ pub async fn export_video(
     println!("[scan] Parcours du dossier: {:?}", folder.canonicalize().unwrap_or_else(|_| folder.to_path_buf()));
     
     let mut files: Vec<_> = fs::read_dir(folder)
-        .map_err(|e| format!("Erreur lecture dossier: {}", e))?
+        .map_err(|e| ExportError::io(format!("Erreur lecture dossier: {}", e)))?
         .filter_map(|entry| {
             let entry = entry.ok()?;
             let path = entry.path();
@@ -1138,7 +2396,7 @@ pub async fn export_video(
     println!("[scan] {} image(s) trouvée(s)", files.len());
     
     if files.is_empty() {
-        return Err("Aucune image .png trouvée dans imgs_folder".to_string());
+        return Err(ExportError::NoFramesFound { folder: imgs_folder });
     }
     
     let first_stem = files[0]
@@ -1148,7 +2406,9 @@ pub async fn export_video(
         .unwrap_or(-1);
     
     if first_stem != 0 {
-        return Err("La première image doit être '0.png' (timestamp 0 ms).".to_string());
+        return Err(ExportError::FirstFrameNotZero {
+            first_frame: files[0].file_name().unwrap_or_default().to_string_lossy().to_string(),
+        });
     }
     
     // Timeline et chemins
@@ -1174,8 +2434,8 @@ pub async fn export_video(
     // Taille cible = taille de 0.png
     println!("[image] Ouverture de la première image pour taille cible...");
     let target_size = {
-        let img_data = fs::read(&files[0]).map_err(|e| format!("Erreur lecture image: {}", e))?;
-        let img = image::load_from_memory(&img_data).map_err(|e| format!("Erreur décodage image: {}", e))?;
+        let img_data = fs::read(&files[0]).map_err(|e| ExportError::io(format!("Erreur lecture image: {}", e)))?;
+        let img = image::load_from_memory(&img_data).map_err(|e| ExportError::invalid_input(format!("Erreur décodage image: {}", e)))?;
         (img.width() as i32, img.height() as i32)
     };
     
@@ -1189,44 +2449,59 @@ pub async fn export_video(
     println!("[timeline] Durée totale: {} ms ({:.3} s)", total_duration_ms, duration_s);
     println!("[perf] Préparation terminée en {:.0} ms", t0.elapsed().as_millis());
     
+    let final_file_path = match overlay_format {
+        Some(format) => format.output_path(&final_file_path).to_string_lossy().to_string(),
+        None => output_format.output_path(&final_file_path),
+    };
+    
     let out_path = Path::new(&final_file_path);
     if let Some(parent) = out_path.parent() {
         println!("[fs] Création du dossier de sortie si besoin: {:?}", parent);
-        fs::create_dir_all(parent).map_err(|e| format!("Erreur création dossier: {}", e))?;
+        fs::create_dir_all(parent).map_err(|e| ExportError::io(format!("Erreur création dossier: {}", e)))?;
     }
     
     let imgs_folder_resolved = folder.canonicalize()
-        .map_err(|e| format!("Erreur résolution chemin: {}", e))?
+        .map_err(|e| ExportError::io(format!("Erreur résolution chemin: {}", e)))?
         .to_string_lossy()
         .to_string();
     
-    let out_path_str = out_path.to_string_lossy().to_string();
-    let audios_vec = audios.unwrap_or_default();
-    let videos_vec = videos.unwrap_or_default();
-    let app_handle = app.clone();
-    let export_id_clone = export_id.clone();
-    
-    task::spawn_blocking(move || {
-        build_and_run_ffmpeg_filter_complex(
-            &export_id_clone,
-            &out_path_str,
-            &path_strs,
-            &ts,
-            target_size,
-            fps,
-            fade_ms,
-            start_time,
-            &audios_vec,
-            &videos_vec,
-            true,
-            Some(&imgs_folder_resolved),
-            duration,
-            chunk_index,
-            app_handle,
-        )
-    }).await
-    .map_err(|e| format!("Erreur tâche: {}", e))?
-    .map_err(|e| format!("Erreur ffmpeg: {}", e))?;
+    // La séquence PNG est écrite dans un dossier, une image par frame
+    let out_path_str = if overlay_format == Some(OverlayFormat::PngSequence) {
+        fs::create_dir_all(out_path).map_err(|e| ExportError::io(format!("Erreur création dossier: {}", e)))?;
+        out_path.join("%06d.png").to_string_lossy().to_string()
+    } else {
+        out_path.to_string_lossy().to_string()
+    };
+    let audio_layers_vec = merge_audio_layers(audios, audio_layers);
+    let videos_vec: Vec<BackgroundClip> = videos.unwrap_or_default().into_iter().map(BackgroundClip::from).collect();
+    
+    let token = export_token(export_id);
+    token.track_output(&final_file_path);
+    
+    let loudness = build_and_run_ffmpeg_filter_complex(
+        export_id,
+        project_id.as_deref(),
+        &out_path_str,
+        &path_strs,
+        &ts,
+        target_size,
+        fps,
+        fade_ms,
+        start_time,
+        &audio_layers_vec,
+        &videos_vec,
+        true,
+        Some(&imgs_folder_resolved),
+        duration,
+        chunk_index,
+        soft_subtitles.as_ref(),
+        overlay_format,
+        &output_format,
+        encoding_profile.as_ref(),
+        progress_tracker,
+        app.clone(),
+    )?;
+    token.output_done(&final_file_path);
     
     let export_time_s = t0.elapsed().as_secs_f64();
     *LAST_EXPORT_TIME_S.lock().unwrap() = Some(export_time_s);
@@ -1247,6 +2522,21 @@ pub async fn export_video(
         "fullPath": final_file_path
     });
     
+    // Taille réelle du fichier, comparée à la taille cible si elle est demandée
+    if let Some(meta) = fs::metadata(&final_file_path).ok().filter(|m| m.is_file()) {
+        completion_data["fileSize"] = serde_json::json!(meta.len());
+        if let Some(target_mb) = encoding_profile.as_ref().and_then(|p| p.target_size_mb()) {
+            let size_mb = meta.len() as f64 / (1024.0 * 1024.0);
+            println!("[done] Taille obtenue: {:.2} Mo (cible {:.2} Mo)", size_mb, target_mb);
+            completion_data["targetSizeMb"] = serde_json::json!(target_mb);
+        }
+    }
+    
+    // Volume mesuré avant normalisation
+    if let (Some(options), Some(measured)) = (output_format.loudness.as_ref(), loudness) {
+        completion_data["loudness"] = options.report(&measured);
+    }
+    
     // Ajouter chunk_index si fourni
     if let Some(chunk_idx) = chunk_index {
         completion_data["chunkIndex"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
@@ -1259,30 +2549,83 @@ pub async fn export_video(
 }
 
 // Fonctions utilitaires pour parser la progression FFmpeg
-fn extract_time_from_ffmpeg_line(line: &str) -> Option<String> {
-    // Chercher "time=" dans la ligne et extraire la valeur
-    if let Some(start) = line.find("time=") {
-        let start = start + 5; // Longueur de "time="
-        if let Some(end) = line[start..].find(char::is_whitespace) {
-            return Some(line[start..start + end].to_string());
-        } else {
-            // Si pas d'espace trouvé, prendre jusqu'à la fin
-            return Some(line[start..].to_string());
-        }
+/// Étape de l'export, indiquée dans chaque événement `export-progress`
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExportPhase {
+    PreprocessingBackgrounds,
+    MeasuringLoudness,
+    Encoding,
+    Concatenating,
+}
+
+/// Signale le début d'une étape, sans progression chiffrée
+pub(crate) fn emit_export_phase(app_handle: &tauri::AppHandle, export_id: &str, chunk_index: Option<i32>, phase: ExportPhase) {
+    let mut phase_data = serde_json::json!({
+        "export_id": export_id,
+        "phase": phase,
+        "current_time": 0.0
+    });
+    if let Some(chunk_idx) = chunk_index {
+        phase_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
     }
-    
-    // Aussi chercher le format "out_time_ms=" pour -progress pipe
-    if let Some(start) = line.find("out_time_ms=") {
-        let start = start + 12; // Longueur de "out_time_ms="
-        if let Some(end) = line[start..].find(char::is_whitespace) {
-            if let Ok(ms) = line[start..start + end].parse::<i64>() {
-                let seconds = ms as f64 / 1_000_000.0; // microseconds to seconds
-                return Some(format!("{:.3}", seconds));
+    let _ = app_handle.emit("export-progress", phase_data);
+}
+
+/// Un bloc de `-progress pipe:2` (lignes clé=valeur)
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub(crate) struct FfmpegProgress {
+    pub frame: Option<u64>,
+    pub fps: Option<f64>,
+    pub bitrate_kbps: Option<f64>,
+    /// Octets écrits jusqu'ici
+    pub total_size: Option<u64>,
+    /// Vitesse par rapport au temps réel (2.3 = 2.3x)
+    pub speed: Option<f64>,
+    /// Position encodée, en secondes
+    pub out_time_s: Option<f64>,
+}
+
+impl FfmpegProgress {
+    /// Applique une ligne `clé=valeur`. Renvoie vrai quand le bloc est complet (`progress=continue|end`).
+    fn apply_line(&mut self, line: &str) -> bool {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            return false;
+        };
+        // Ignore les lignes de -stats ("frame=  120 fps= 30 ...")
+        if key.contains(char::is_whitespace) || value.trim().contains(char::is_whitespace) {
+            return false;
+        }
+        let value = value.trim();
+        
+        match key {
+            "frame" => self.frame = value.parse().ok(),
+            "fps" => self.fps = value.parse().ok(),
+            "bitrate" => self.bitrate_kbps = value.trim_end_matches("kbits/s").parse().ok(),
+            "total_size" => self.total_size = value.parse().ok(),
+            "speed" => self.speed = value.trim_end_matches('x').parse().ok(),
+            // out_time_ms est aussi en microsecondes, malgré son nom
+            // Avant la 1re image, ffmpeg écrit une position négative (ex: -9223372036854775807)
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<i64>().map(|us| us.max(0)) {
+                    self.out_time_s = Some(us as f64 / 1_000_000.0);
+                }
             }
+            "out_time" if value != "N/A" => self.out_time_s = Some(parse_ffmpeg_time(value).max(0.0)),
+            "progress" => return true,
+            _ => {}
         }
+        false
+    }
+}
+
+/// Temps restant estimé : d'après la vitesse d'encodage, sinon d'après le temps écoulé
+fn estimate_eta(remaining_s: f64, speed: Option<f64>, done_s: f64, started: Instant) -> Option<f64> {
+    match speed {
+        Some(speed) if speed > 0.0 => Some(remaining_s / speed),
+        _ if done_s > 0.0 => Some(started.elapsed().as_secs_f64() * remaining_s / done_s),
+        _ => None,
     }
-    
-    None
 }
 
 fn parse_ffmpeg_time(time_str: &str) -> f64 {
@@ -1303,133 +2646,446 @@ fn parse_ffmpeg_time(time_str: &str) -> f64 {
 }
 
 #[tauri::command]
-pub fn cancel_export(export_id: String) -> Result<String, String> {
+pub fn cancel_export(export_id: String) -> Result<String, ExportError> {
     println!("[cancel_export] Demande d'annulation pour export_id: {}", export_id);
     
-    let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
+    let token = ACTIVE_EXPORTS
+        .lock()
+        .map_err(|_| ExportError::io("Failed to lock active exports"))?
+        .get(&export_id)
+        .cloned();
     
-    if let Some(process_ref) = active_exports.remove(&export_id) {
-        if let Ok(mut process_guard) = process_ref.lock() {
-            if let Some(mut child) = process_guard.take() {
-                match child.kill() {
-                    Ok(_) => {
-                        println!("[cancel_export] Processus FFmpeg tué avec succès pour export_id: {}", export_id);
-                        let _ = child.wait(); // Nettoyer le processus zombie
-                        Ok(format!("Export {} annulé avec succès", export_id))
-                    },
-                    Err(e) => {
-                        println!("[cancel_export] Erreur lors de l'arrêt du processus: {:?}", e);
-                        Err(format!("Erreur lors de l'annulation: {}", e))
-                    }
-                }
-            } else {
-                println!("[cancel_export] Aucun processus actif trouvé pour export_id: {}", export_id);
-                Err(format!("Aucun processus actif pour l'export {}", export_id))
-            }
-        } else {
-            Err("Failed to lock process".to_string())
-        }
-    } else {
+    let Some(token) = token else {
         println!("[cancel_export] Export_id non trouvé dans les exports actifs: {}", export_id);
-        Err(format!("Export {} non trouvé ou déjà terminé", export_id))
-    }
+        return Err(ExportError::ExportNotFound { export_id });
+    };
+    
+    // Les étapes suivantes (chunks, concaténation) ne seront pas lancées ; le nettoyage est fait par l'export lui-même
+    let killed = token.cancel();
+    println!("[cancel_export] {} processus FFmpeg arrêté(s) pour export_id: {}", killed, export_id);
+    Ok(format!("Export {} annulé avec succès", export_id))
+}
+
+/// Met en pause ou relance tous les processus ffmpeg d'un export
+fn set_export_paused(export_id: String, paused: bool, app: &tauri::AppHandle) -> Result<String, ExportError> {
+    let token = ACTIVE_EXPORTS
+        .lock()
+        .map_err(|_| ExportError::io("Failed to lock active exports"))?
+        .get(&export_id)
+        .cloned();
+    
+    let Some(token) = token else {
+        println!("[pause_export] Export_id non trouvé dans les exports actifs: {}", export_id);
+        return Err(ExportError::ExportNotFound { export_id });
+    };
+    
+    let count = token.set_paused(paused);
+    println!(
+        "[pause_export] {} processus FFmpeg {} pour export_id: {}",
+        count, if paused { "suspendu(s)" } else { "relancé(s)" }, export_id
+    );
+    
+    // ffmpeg n'écrit plus rien pendant la pause : l'état est signalé tout de suite
+    let _ = app.emit("export-progress", serde_json::json!({
+        "export_id": export_id,
+        "paused": paused,
+        "current_time": 0.0
+    }));
+    
+    Ok(format!("Export {} {}", export_id, if paused { "mis en pause" } else { "repris" }))
+}
+
+#[tauri::command]
+pub fn pause_export(export_id: String, app: tauri::AppHandle) -> Result<String, ExportError> {
+    set_export_paused(export_id, true, &app)
+}
+
+#[tauri::command]
+pub fn resume_export(export_id: String, app: tauri::AppHandle) -> Result<String, ExportError> {
+    set_export_paused(export_id, false, &app)
 }
 
 #[tauri::command]
 pub async fn concat_videos(
     video_paths: Vec<String>,
     output_path: String,
-) -> Result<String, String> {
+    soft_subtitles: Option<SoftSubtitleOptions>,
+    start_time: Option<i32>,
+    export_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<String, ExportError> {
+    // L'export_id rattache la concaténation à l'export : annulation et pause l'atteignent aussi.
+    // Sans export_id (anciens appels), la concaténation a son propre identifiant.
+    let export_id = export_id.unwrap_or_else(|| {
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        format!("concat-{}", now_ms)
+    });
+    task::spawn_blocking(move || {
+        run_cancellable(&export_id, &app, || {
+            concat_files(video_paths, output_path, soft_subtitles, start_time, &export_id, &app)
+        })
+    })
+    .await
+    .map_err(|e| ExportError::io(format!("Erreur tâche: {}", e)))?
+}
+
+/// Copie un fichier par blocs : l'annulation l'interrompt et la pause la suspend
+fn copy_cancellable(src: &str, dst: &str, token: &CancellationToken, export_id: &str) -> Result<(), ExportError> {
+    if Path::new(src) == Path::new(dst) {
+        return Ok(());
+    }
+    let copy_error = |e: std::io::Error| ExportError::io(format!("Erreur lors de la copie: {}", e));
+    let mut reader = fs::File::open(src).map_err(copy_error)?;
+    let mut writer = fs::File::create(dst).map_err(copy_error)?;
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        while token.is_paused() && !token.is_cancelled() {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        if token.is_cancelled() {
+            return Err(ExportError::Cancelled { export_id: export_id.to_string() });
+        }
+        let n = reader.read(&mut buffer).map_err(copy_error)?;
+        if n == 0 {
+            return Ok(());
+        }
+        writer.write_all(&buffer[..n]).map_err(copy_error)?;
+    }
+}
+
+/// Concatène les vidéos sans réencodage, en ajoutant les sous-titres soft s'il y en a
+pub(crate) fn concat_files(
+    video_paths: Vec<String>,
+    output_path: String,
+    soft_subtitles: Option<SoftSubtitleOptions>,
+    start_time: Option<i32>,
+    export_id: &str,
+    app: &tauri::AppHandle,
+) -> Result<String, ExportError> {
     println!("[concat_videos] Début de la concaténation de {} vidéos", video_paths.len());
     println!("[concat_videos] Fichier de sortie: {}", output_path);
     
     if video_paths.is_empty() {
-        return Err("Aucune vidéo fournie pour la concaténation".to_string());
-    }
-    
-    if video_paths.len() == 1 {
-        // Si une seule vidéo, on peut simplement la copier ou la renommer
-        println!("[concat_videos] Une seule vidéo, copie vers le fichier final");
-        std::fs::copy(&video_paths[0], &output_path)
-            .map_err(|e| format!("Erreur lors de la copie: {}", e))?;
-        return Ok(output_path);
+        return Err(ExportError::invalid_input("Aucune vidéo fournie pour la concaténation"));
     }
     
     // Créer le dossier de sortie si nécessaire
     if let Some(parent) = Path::new(&output_path).parent() {
         fs::create_dir_all(parent)
-            .map_err(|e| format!("Erreur création dossier de sortie: {}", e))?;
+            .map_err(|e| ExportError::io(format!("Erreur création dossier de sortie: {}", e)))?;
+    }
+    
+    let token = export_token(export_id);
+    token.track_output(&output_path);
+    
+    if video_paths.len() == 1 && soft_subtitles.is_none() {
+        // Si une seule vidéo, on peut simplement la copier
+        println!("[concat_videos] Une seule vidéo, copie vers le fichier final");
+        copy_cancellable(&video_paths[0], &output_path, &token, export_id)?;
+        token.output_done(&output_path);
+        return Ok(output_path);
     }
     
     // Créer un fichier de liste temporaire pour FFmpeg
     let temp_dir = std::env::temp_dir();
-    let list_file_path = temp_dir.join(format!("concat_list_{}.txt", 
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()));
+    let list_file_path = temp_dir.join(format!("concat_list_{}.txt", export_id));
     
     // Écrire la liste des fichiers à concaténer
     let mut list_content = String::new();
     for video_path in &video_paths {
         // Vérifier que le fichier existe
         if !Path::new(video_path).exists() {
-            return Err(format!("Fichier vidéo non trouvé: {}", video_path));
+            return Err(ExportError::invalid_input(format!("Fichier vidéo non trouvé: {}", video_path)));
         }
         list_content.push_str(&format!("file '{}'\n", video_path));
     }
     
     fs::write(&list_file_path, list_content)
-        .map_err(|e| format!("Erreur écriture fichier liste: {}", e))?;
+        .map_err(|e| ExportError::io(format!("Erreur écriture fichier liste: {}", e)))?;
     
     println!("[concat_videos] Fichier liste créé: {:?}", list_file_path);
     
+    // Sous-titres soft : ajoutés ici plutôt que dans chaque chunk
+    let soft_subs = match soft_subtitles {
+        Some(ref options) => {
+            let video_size = ffprobe_video_size(&video_paths[0]).unwrap_or((1920, 1080));
+            subtitles::prepare_soft_subtitles(
+                options,
+                export_id,
+                &output_path,
+                start_time.unwrap_or(0) as i64,
+                None,
+                video_size,
+                &temp_dir,
+            )?
+        }
+        None => None,
+    };
+    
     // Préparer la commande FFmpeg
-    let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+    let ffmpeg_exe = ffmpeg_binary()?;
+    
+    let mut cmd = vec![
+        ffmpeg_exe,
+        "-y".to_string(),                           // Écraser le fichier de sortie
+        "-hide_banner".to_string(),                 // Masquer le banner FFmpeg
+        "-loglevel".to_string(), "info".to_string(), // Niveau de log
+        "-progress".to_string(), "pipe:2".to_string(), // Progression lisible sur stderr
+        "-f".to_string(), "concat".to_string(),     // Format d'entrée concat
+        "-safe".to_string(), "0".to_string(),       // Permettre les chemins absolus
+        "-i".to_string(), list_file_path.to_string_lossy().to_string(), // Fichier de liste
+    ];
     
-    let mut cmd = Command::new(&ffmpeg_exe);
-    cmd.args(&[
-        "-y",                           // Écraser le fichier de sortie
-        "-hide_banner",                 // Masquer le banner FFmpeg
-        "-loglevel", "info",           // Niveau de log
-        "-f", "concat",                // Format d'entrée concat
-        "-safe", "0",                  // Permettre les chemins absolus
-        "-i", &list_file_path.to_string_lossy(), // Fichier de liste
-        "-c", "copy",                  // Copier sans réencodage
-        &output_path                   // Fichier de sortie
-    ]);
+    if let Some(ref subs) = soft_subs {
+        cmd.extend(subs.input_args());
+        cmd.extend(["-map", "0:v", "-map", "0:a?"].map(String::from));
+    }
     
-    // Configurer la commande pour cacher les fenêtres CMD sur Windows
-    configure_command_no_window(&mut cmd);
+    cmd.extend(["-c".to_string(), "copy".to_string()]); // Copier sans réencodage
+    
+    if let Some(ref subs) = soft_subs {
+        cmd.extend(subs.output_args(1));
+    }
+    
+    cmd.push(output_path.clone());              // Fichier de sortie
     
     println!("[concat_videos] Exécution de FFmpeg...");
     
-    let output = cmd.output()
-        .map_err(|e| format!("Erreur exécution FFmpeg: {}", e))?;
+    let total_s: f64 = video_paths.iter().map(|p| ffprobe_duration_sec(p)).sum();
+    let result = run_ffmpeg_with_progress(
+        &cmd,
+        None,
+        export_id,
+        total_s,
+        (0, 1),
+        ExportPhase::Concatenating,
+        None,
+        Some(&output_path),
+        None,
+        app,
+    );
     
     // Nettoyer le fichier temporaire
     let _ = fs::remove_file(&list_file_path);
+    if let Some(ref subs) = soft_subs {
+        subs.cleanup();
+    }
     
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        
-        println!("[concat_videos] Erreur FFmpeg:");
-        println!("STDOUT: {}", stdout);
-        println!("STDERR: {}", stderr);
-        
-        return Err(format!(
-            "FFmpeg a échoué lors de la concaténation (code: {:?})\nSTDERR: {}",
-            output.status.code(),
-            stderr
-        ));
+    match result {
+        Ok(_) => {}
+        Err(ExportError::EncoderFailed { exit_code, stderr, .. }) => {
+            println!("[concat_videos] Erreur FFmpeg:");
+            println!("STDERR: {}", stderr);
+            return Err(ExportError::ConcatFailed { exit_code, stderr });
+        }
+        Err(e) => return Err(e),
     }
     
     // Vérifier que le fichier de sortie a été créé
     if !Path::new(&output_path).exists() {
-        return Err("Le fichier de sortie n'a pas été créé".to_string());
+        return Err(ExportError::io("Le fichier de sortie n'a pas été créé"));
     }
     
+    token.output_done(&output_path);
     println!("[concat_videos] ✅ Concaténation réussie: {}", output_path);
     Ok(output_path)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_block(lines: &[&str]) -> (FfmpegProgress, bool) {
+        let mut progress = FfmpegProgress::default();
+        let mut complete = false;
+        for line in lines {
+            complete = progress.apply_line(line);
+        }
+        (progress, complete)
+    }
+
+    #[test]
+    fn progress_block_is_parsed() {
+        let (progress, complete) = parse_block(&[
+            "frame=240",
+            "fps=59.94",
+            "bitrate=1523.4kbits/s",
+            "total_size=1900544",
+            "out_time_us=8000000",
+            "out_time=00:00:08.000000",
+            "speed=2.01x",
+            "progress=continue",
+        ]);
+        assert!(complete);
+        assert_eq!(progress.frame, Some(240));
+        assert_eq!(progress.fps, Some(59.94));
+        assert_eq!(progress.bitrate_kbps, Some(1523.4));
+        assert_eq!(progress.total_size, Some(1_900_544));
+        assert_eq!(progress.speed, Some(2.01));
+        assert_eq!(progress.out_time_s, Some(8.0));
+    }
+
+    #[test]
+    fn progress_block_ends_only_on_progress_line() {
+        let mut progress = FfmpegProgress::default();
+        assert!(!progress.apply_line("frame=1"));
+        assert!(!progress.apply_line("out_time_ms=500000"));
+        assert!(progress.apply_line("progress=end"));
+        // out_time_ms est en microsecondes
+        assert_eq!(progress.out_time_s, Some(0.5));
+    }
+
+    #[test]
+    fn progress_ignores_stats_and_log_lines() {
+        let (progress, complete) = parse_block(&[
+            "frame=  120 fps= 30 q=28.0 size=     512kB time=00:00:04.00 bitrate=1048.6kbits/s speed=1.5x",
+            "[libx264 @ 0x55d5] using cpu capabilities: MMX2 SSE2Fast",
+            "Stream mapping:",
+        ]);
+        assert!(!complete);
+        assert_eq!(progress.frame, None);
+        assert_eq!(progress.out_time_s, None);
+        assert_eq!(progress.speed, None);
+    }
+
+    #[test]
+    fn progress_handles_missing_values() {
+        let (progress, _) = parse_block(&[
+            "bitrate=N/A",
+            "speed=N/A",
+            "out_time_us=-9223372036854775807",
+            "out_time=N/A",
+        ]);
+        assert_eq!(progress.bitrate_kbps, None);
+        assert_eq!(progress.speed, None);
+        assert_eq!(progress.out_time_s, Some(0.0));
+    }
+
+    #[test]
+    fn ffmpeg_time_formats() {
+        assert_eq!(parse_ffmpeg_time("01:02:03.5"), 3723.5);
+        assert_eq!(parse_ffmpeg_time("12.25"), 12.25);
+        assert_eq!(parse_ffmpeg_time("garbage"), 0.0);
+    }
+
+    #[test]
+    fn chunk_spec_from_the_frontend_keeps_clip_positions() {
+        // Charge utile envoyée par getChunkSpec (src/routes/exporter/+page.svelte)
+        let spec: ExportChunkSpec = serde_json::from_value(serde_json::json!({
+            "chunk_index": 1,
+            "imgs_folder": "/tmp/exports/42/chunk_1",
+            "final_file_path": "/tmp/exports/42/chunk_1_video.mp4",
+            "fps": 30,
+            "fade_duration": 150,
+            "start_time": 60000,
+            "duration": 30000,
+            "audios": [
+                { "path": "/a/recitation.mp3", "timeline_start_ms": 0, "timeline_end_ms": 45000, "source_in_ms": 0, "source_out_ms": 45000 },
+                { "path": "/a/suite.mp3", "timeline_start_ms": 50000, "timeline_end_ms": 90000, "source_in_ms": 0, "source_out_ms": 40000 }
+            ],
+            "videos": [
+                { "path": "/v/bg.mp4", "timeline_start_ms": 2000, "timeline_end_ms": 80000, "source_in_ms": 0, "source_out_ms": 78000 }
+            ],
+            "project_id": "1234"
+        }))
+        .unwrap();
+
+        let audios: Vec<audio::AudioClip> = spec.audios.unwrap().into_iter().map(audio::AudioClip::from).collect();
+        assert_eq!(audios.len(), 2);
+        assert_eq!(audios[1].path, "/a/suite.mp3");
+        assert_eq!(audios[1].timeline_start_ms, Some(50000));
+        assert_eq!(audios[1].timeline_end_ms, Some(90000));
+        assert_eq!(audios[1].source_in_ms, Some(0));
+        assert_eq!(audios[1].source_out_ms, Some(40000));
+
+        let videos: Vec<BackgroundClip> = spec.videos.unwrap().into_iter().map(BackgroundClip::from).collect();
+        assert_eq!(videos[0].timeline_start_ms, Some(2000));
+        assert_eq!(videos[0].timeline_end_ms, Some(80000));
+        assert_eq!(videos[0].trim_in_ms, Some(0));
+        assert_eq!(videos[0].trim_out_ms, Some(78000));
+        assert_eq!(spec.project_id.as_deref(), Some("1234"));
+    }
+
+    #[test]
+    fn chunk_spec_still_accepts_plain_paths() {
+        let spec: ExportChunkSpec = serde_json::from_value(serde_json::json!({
+            "chunk_index": null,
+            "imgs_folder": "/tmp/exports/42",
+            "final_file_path": "/tmp/out.mp4",
+            "fps": 30,
+            "fade_duration": 150,
+            "start_time": 0,
+            "duration": null,
+            "audios": ["/a/recitation.mp3"],
+            "videos": ["/v/bg.mp4"]
+        }))
+        .unwrap();
+
+        let audio = audio::AudioClip::from(spec.audios.unwrap().remove(0));
+        assert_eq!(audio.path, "/a/recitation.mp3");
+        assert_eq!(audio.timeline_start_ms, None);
+        let video = BackgroundClip::from(spec.videos.unwrap().remove(0));
+        assert_eq!(video.path, "/v/bg.mp4");
+        assert_eq!(video.timeline_start_ms, None);
+    }
+
+    #[test]
+    fn target_size_selects_a_two_pass_software_encoder() {
+        let profile = crate::encoding_profile::EncodingPreset::Whatsapp.profile().for_duration(60.0, true, false).unwrap();
+        for family in [VideoCodec::H264, VideoCodec::Hevc, VideoCodec::Av1, VideoCodec::Vp9] {
+            let (encoder, _, _) = choose_codec(family, true, None, true);
+            assert!(!encoder_backend::is_hw_encoder(&encoder), "{:?} -> {}", family, encoder);
+            assert!(profile.needs_two_runs(&encoder), "{:?} -> {}", family, encoder);
+        }
+    }
+
+    #[test]
+    fn profile_args_are_merged_into_codec_params() {
+        let (_, params, _) = software_codec(VideoCodec::Vp9, false);
+        let merged = merge_codec_args(&params, vec![
+            "-b:v".to_string(), "2000k".to_string(),
+            "-cpu-used".to_string(), "2".to_string(),
+        ]);
+        assert_eq!(
+            merged,
+            ["-pix_fmt", "yuv420p", "-deadline", "good", "-row-mt", "1", "-b:v", "2000k", "-cpu-used", "2"]
+        );
+
+        let hw = vec!["-pix_fmt".to_string(), "nv12".to_string(), "-rc".to_string(), "vbr".to_string()];
+        let merged = merge_codec_args(&hw, vec!["-global_quality".to_string(), "23".to_string()]);
+        assert_eq!(merged, ["-pix_fmt", "nv12", "-global_quality", "23"]);
+    }
+
+    #[test]
+    fn looped_layers_end_with_the_export_not_the_chunk() {
+        let chunk = |index: i32, start: i32| -> ExportChunkSpec {
+            serde_json::from_value(serde_json::json!({
+                "chunk_index": index,
+                "imgs_folder": format!("/tmp/exports/42/chunk_{}", index),
+                "final_file_path": format!("/tmp/exports/42/chunk_{}_video.mp4", index),
+                "fps": 30,
+                "fade_duration": 150,
+                "start_time": start,
+                "duration": 10000,
+                "audios": null,
+                "videos": null,
+                "audio_layers": [
+                    { "clips": ["/a/nasheed.mp3"], "loop_until_end": true, "fade_out_ms": 2000 },
+                    { "clips": ["/a/ambiance.mp3"], "loop_until_end": true, "end_ms": 12000 }
+                ]
+            }))
+            .unwrap()
+        };
+        let mut specs = vec![chunk(0, 0), chunk(1, 10000), chunk(2, 20000)];
+        pin_loop_layer_ends(&mut specs);
+
+        for spec in &specs {
+            let layers = spec.audio_layers.as_ref().unwrap();
+            assert_eq!(layers[0].end_ms, Some(30000));
+            assert_eq!(layers[1].end_ms, Some(12000));
+        }
+    }
+}
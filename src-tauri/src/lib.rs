@@ -3,7 +3,16 @@ use std::path::{Path};
 use std::process::Command;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+mod audio;
+mod background;
+mod encoder_backend;
+mod encoders;
+mod encoding_profile;
 mod exporter;
+mod export_queue;
+mod loudness;
+mod preproc_cache;
+mod subtitles;
 use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
 
 use font_kit::source::SystemSource;
@@ -720,8 +729,23 @@ pub fn run() {
             open_explorer_with_file_selected,
             get_video_dimensions,
             exporter::export_video,
+            exporter::export_chunks,
             exporter::cancel_export,
+            exporter::pause_export,
+            exporter::resume_export,
             exporter::concat_videos,
+            export_queue::enqueue_export_job,
+            export_queue::list_export_jobs,
+            export_queue::resume_export_job,
+            export_queue::discard_export_job,
+            subtitles::export_subtitles,
+            encoding_profile::list_encoding_presets,
+            encoders::get_encoder_capabilities,
+            encoders::invalidate_encoder_cache,
+            preproc_cache::get_preproc_cache_stats,
+            preproc_cache::purge_preproc_cache,
+            preproc_cache::purge_preproc_cache_for_project,
+            preproc_cache::set_preproc_cache_limit,
             convert_audio_to_cbr,
             init_discord_rpc,
             update_discord_activity,
@@ -736,6 +760,8 @@ pub fn run() {
                         .build(),
                 )?;
             }
+            encoders::init(app.handle());
+            export_queue::init(app.handle().clone());
             Ok(())
         })
         .run(tauri::generate_context!())
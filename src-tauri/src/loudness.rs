@@ -0,0 +1,294 @@
+use crate::audio::{self, AudioLayer, SourceDurations};
+use crate::exporter::{self, ExportError, ExportPhase};
+
+/// Normalisation du volume (EBU R128) : mesure avec `loudnorm`, puis gain fixe et limiteur
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LoudnessOptions {
+    /// Loudness intégrée visée, en LUFS (-14 pour YouTube, -16 pour les podcasts)
+    #[serde(default = "default_target_lufs")]
+    pub target_lufs: f64,
+    /// Plafond des crêtes réelles, en dBTP (estimées par suréchantillonnage 4x, comme BS.1770)
+    #[serde(default = "default_true_peak_db")]
+    pub true_peak_db: f64,
+    /// Plage de loudness visée, en LU
+    #[serde(default = "default_lra")]
+    pub lra: f64,
+    /// Mesure de la 1re passe, partagée par tous les chunks d'un export
+    #[serde(default)]
+    pub measured: Option<LoudnessMeasurement>,
+}
+
+fn default_target_lufs() -> f64 {
+    -14.0
+}
+
+fn default_true_peak_db() -> f64 {
+    -1.0
+}
+
+fn default_lra() -> f64 {
+    11.0
+}
+
+/// Réduction maximale des crêtes laissée au limiteur pour atteindre la loudness visée (dB)
+const MAX_LIMITER_REDUCTION_DB: f64 = 6.0;
+
+/// Valeurs mesurées par la 1re passe de `loudnorm`
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct LoudnessMeasurement {
+    pub input_i: f64,
+    pub input_tp: f64,
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+/// Bloc JSON affiché par `loudnorm=print_format=json` (nombres écrits en chaînes)
+#[derive(serde::Deserialize)]
+struct LoudnormOutput {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+impl LoudnessOptions {
+    fn target_lufs(&self) -> f64 {
+        self.target_lufs.clamp(-70.0, -5.0)
+    }
+
+    fn true_peak_db(&self) -> f64 {
+        self.true_peak_db.clamp(-9.0, 0.0)
+    }
+
+    fn targets(&self) -> String {
+        format!(
+            "I={:.1}:TP={:.1}:LRA={:.1}",
+            self.target_lufs(),
+            self.true_peak_db(),
+            self.lra.clamp(1.0, 50.0)
+        )
+    }
+
+    /// Gain à appliquer (dB), et vrai si la loudness visée est atteinte.
+    ///
+    /// Au-delà de `MAX_LIMITER_REDUCTION_DB` de réduction des crêtes, le gain est plafonné
+    /// plutôt que d'écraser la dynamique : la vidéo reste alors sous la cible.
+    pub(crate) fn gain_db(&self, m: &LoudnessMeasurement) -> (f64, bool) {
+        let wanted = self.target_lufs() - m.input_i;
+        let max_gain = self.true_peak_db() - m.input_tp + MAX_LIMITER_REDUCTION_DB;
+        if wanted > max_gain {
+            (max_gain, false)
+        } else {
+            (wanted, true)
+        }
+    }
+
+    /// Filtre de la 1re passe : mesure seulement
+    fn analysis_filter(&self) -> String {
+        format!("loudnorm={}:print_format=json", self.targets())
+    }
+
+    /// Filtre de la 2e passe : gain fixe d'après la mesure, puis limiteur au plafond des crêtes.
+    ///
+    /// `loudnorm` en mode linéaire repasse en mode dynamique quand les crêtes dépasseraient
+    /// le plafond, ce qui donnerait un gain différent à chaque chunk : le gain est donc appliqué
+    /// directement, identique pour tous les chunks d'un export.
+    ///
+    /// `alimiter` ne voit que les échantillons : il travaille à 192 kHz (4x) pour limiter aussi
+    /// les crêtes entre échantillons, comme la mesure de crête réelle de BS.1770.
+    pub(crate) fn normalize_filter(&self, m: &LoudnessMeasurement) -> String {
+        let (gain_db, _) = self.gain_db(m);
+        format!(
+            "volume={:.2}dB,aresample=192000,alimiter=limit={:.6}:level=0:attack=5:release=50,aresample=48000",
+            gain_db,
+            10f64.powf(self.true_peak_db() / 20.0)
+        )
+    }
+
+    /// Mesure, cible et gain appliqué, pour l'événement `export-complete`
+    pub(crate) fn report(&self, m: &LoudnessMeasurement) -> serde_json::Value {
+        let (gain_db, target_reached) = self.gain_db(m);
+        serde_json::json!({
+            "targetLufs": self.target_lufs,
+            // Plafond visé, tenu sur le signal suréchantillonné 4x
+            "truePeakDb": self.true_peak_db,
+            "measuredLufs": m.input_i,
+            "measuredTruePeakDb": m.input_tp,
+            "measuredLra": m.input_lra,
+            "appliedGainDb": gain_db,
+            "outputLufs": m.input_i + gain_db,
+            "targetReached": target_reached
+        })
+    }
+}
+
+/// Extrait la mesure du dernier bloc JSON écrit par `loudnorm` sur stderr
+fn parse_measurement(stderr: &str) -> Option<LoudnessMeasurement> {
+    let start = stderr.rfind('{')?;
+    let end = stderr[start..].find('}')? + start;
+    let output: LoudnormOutput = serde_json::from_str(&stderr[start..=end]).ok()?;
+    let value = |s: &str| s.trim().parse::<f64>().ok().filter(|v| v.is_finite());
+    Some(LoudnessMeasurement {
+        input_i: value(&output.input_i)?,
+        input_tp: value(&output.input_tp)?,
+        input_lra: value(&output.input_lra)?,
+        input_thresh: value(&output.input_thresh)?,
+        target_offset: value(&output.target_offset)?,
+    })
+}
+
+/// 1re passe : mixe les couches sur `[start_s, start_s + duration_s]` et mesure le résultat.
+///
+/// Renvoie `None` s'il n'y a aucun audio sur cette plage.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn measure(
+    export_id: &str,
+    layers: &[AudioLayer],
+    durations: &SourceDurations,
+    options: &LoudnessOptions,
+    start_s: f64,
+    duration_s: f64,
+    chunk_index: Option<i32>,
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<LoudnessMeasurement>, ExportError> {
+    let placements: Vec<audio::LayerPlacement> = layers.iter().map(|l| l.place(start_s, duration_s, durations)).collect();
+    if placements.iter().all(|p| p.clips.is_empty()) {
+        return Ok(None);
+    }
+
+    exporter::emit_export_phase(app_handle, export_id, chunk_index, ExportPhase::MeasuringLoudness);
+    println!("[loudness] Mesure sur {:.1}s à partir de {:.1}s", duration_s, start_s);
+
+    let mut cmd = vec![
+        exporter::ffmpeg_binary()?,
+        "-y".to_string(),
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(), "info".to_string(),
+        "-progress".to_string(), "pipe:2".to_string(),
+    ];
+    for clip in placements.iter().flat_map(|p| &p.clips) {
+        cmd.extend_from_slice(&["-i".to_string(), clip.path.clone()]);
+    }
+    let filter = audio::mix_filter(layers, &placements, 0, start_s, duration_s, Some(&options.analysis_filter())).join(";");
+    cmd.extend_from_slice(&[
+        "-filter_complex".to_string(), filter,
+        "-map".to_string(), "[aout]".to_string(),
+        "-f".to_string(), "null".to_string(),
+        "-".to_string(),
+    ]);
+
+    let stderr = exporter::run_ffmpeg_with_progress(
+        &cmd,
+        None,
+        export_id,
+        duration_s,
+        (0, 1),
+        ExportPhase::MeasuringLoudness,
+        chunk_index,
+        None,
+        None,
+        app_handle,
+    )?;
+
+    // Audio entièrement silencieux : rien à normaliser
+    if stderr.contains("\"input_i\" : \"-inf\"") {
+        println!("[loudness] Audio silencieux, normalisation ignorée");
+        return Ok(None);
+    }
+
+    let measurement = parse_measurement(&stderr)
+        .ok_or_else(|| ExportError::io("Mesure du volume illisible dans la sortie de loudnorm"))?;
+    println!(
+        "[loudness] Mesuré: {:.1} LUFS, crête {:.1} dBTP, LRA {:.1} LU (cible {:.1} LUFS)",
+        measurement.input_i, measurement.input_tp, measurement.input_lra, options.target_lufs
+    );
+    let (gain_db, target_reached) = options.gain_db(&measurement);
+    if !target_reached {
+        println!(
+            "[loudness] ⚠️ Cible non atteinte sans écraser les crêtes: gain limité à {:+.1} dB ({:.1} LUFS)",
+            gain_db,
+            measurement.input_i + gain_db
+        );
+    }
+    Ok(Some(measurement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> LoudnessOptions {
+        LoudnessOptions { target_lufs: -14.0, true_peak_db: -1.0, lra: 11.0, measured: None }
+    }
+
+    fn measurement(input_i: f64, input_tp: f64) -> LoudnessMeasurement {
+        LoudnessMeasurement { input_i, input_tp, input_lra: 6.0, input_thresh: input_i - 10.0, target_offset: 0.0 }
+    }
+
+    #[test]
+    fn measurement_is_read_from_the_last_json_block() {
+        let stderr = r#"
+[Parsed_loudnorm_0 @ 0x55d0] 
+{
+	"input_i" : "-23.54",
+	"input_tp" : "-4.20",
+	"input_lra" : "5.80",
+	"input_thresh" : "-33.91",
+	"output_i" : "-14.02",
+	"output_tp" : "-1.00",
+	"output_lra" : "4.90",
+	"output_thresh" : "-24.30",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.02"
+}
+"#;
+        let m = parse_measurement(stderr).unwrap();
+        assert_eq!(m.input_i, -23.54);
+        assert_eq!(m.input_tp, -4.2);
+        assert_eq!(m.input_lra, 5.8);
+        assert_eq!(m.input_thresh, -33.91);
+        assert_eq!(m.target_offset, 0.02);
+    }
+
+    #[test]
+    fn unreadable_measurement_is_rejected() {
+        assert!(parse_measurement("no json here").is_none());
+        let silent = r#"{ "input_i" : "-inf", "input_tp" : "-inf", "input_lra" : "0.00", "input_thresh" : "-70.00", "target_offset" : "inf" }"#;
+        assert!(parse_measurement(silent).is_none());
+    }
+
+    #[test]
+    fn gain_reaches_the_target_with_enough_headroom() {
+        let (gain, reached) = options().gain_db(&measurement(-20.0, -8.0));
+        assert!((gain - 6.0).abs() < 1e-9);
+        assert!(reached);
+    }
+
+    #[test]
+    fn gain_lets_the_limiter_take_a_few_db_of_peaks() {
+        // +9 dB porterait les crêtes à +1 dBTP : le limiteur en retire 2 dB
+        let (gain, reached) = options().gain_db(&measurement(-23.0, -8.0));
+        assert!((gain - 9.0).abs() < 1e-9);
+        assert!(reached);
+    }
+
+    #[test]
+    fn gain_is_capped_when_peaks_would_be_crushed() {
+        let (gain, reached) = options().gain_db(&measurement(-30.0, -2.0));
+        assert!((gain - (-1.0 + 2.0 + MAX_LIMITER_REDUCTION_DB)).abs() < 1e-9);
+        assert!(!reached);
+
+        let report = options().report(&measurement(-30.0, -2.0));
+        assert_eq!(report["targetReached"], false);
+        assert_eq!(report["appliedGainDb"], 7.0);
+        assert_eq!(report["outputLufs"], -23.0);
+    }
+
+    #[test]
+    fn normalize_filter_applies_a_fixed_gain_and_limits_peaks() {
+        let filter = options().normalize_filter(&measurement(-20.0, -8.0));
+        assert_eq!(filter, "volume=6.00dB,aresample=192000,alimiter=limit=0.891251:level=0:attack=5:release=50,aresample=48000");
+    }
+}
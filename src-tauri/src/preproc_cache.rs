@@ -0,0 +1,397 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::exporter::ExportError;
+
+// Taille maximale par défaut du cache de prétraitement (2 Go)
+const DEFAULT_MAX_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+const INDEX_FILE: &str = "index.json";
+
+lazy_static::lazy_static! {
+    static ref CACHE_INDEX: Mutex<Option<CacheIndex>> = Mutex::new(None);
+}
+
+/// Vidéo de fond prétraitée
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CacheEntry {
+    pub file: String,
+    /// Fichier source (vide pour les fichiers trouvés dans le dossier sans être indexés)
+    pub source: String,
+    pub size_bytes: u64,
+    /// Dernière utilisation, en secondes depuis l'epoch (éviction LRU)
+    pub last_used: u64,
+    /// Projets (id du projet) ayant utilisé ce fichier
+    #[serde(default)]
+    pub projects: Vec<String>,
+}
+
+/// Index du cache, enregistré dans `<cache>/index.json`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct CacheIndex {
+    #[serde(default = "default_max_bytes")]
+    max_bytes: u64,
+    /// Entrées par nom de fichier
+    #[serde(default)]
+    entries: HashMap<String, CacheEntry>,
+    /// Fichiers utilisés par les exports en cours (par export_id) : ni évincés ni purgés
+    #[serde(skip)]
+    pinned: HashMap<String, HashSet<String>>,
+}
+
+fn default_max_bytes() -> u64 {
+    DEFAULT_MAX_BYTES
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PreprocCacheStats {
+    pub dir: String,
+    pub total_bytes: u64,
+    pub max_bytes: u64,
+    pub file_count: usize,
+    pub entries: Vec<CacheEntry>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct PurgeResult {
+    pub removed_files: usize,
+    pub freed_bytes: u64,
+}
+
+pub(crate) fn cache_dir() -> PathBuf {
+    std::env::temp_dir().join("qurancaption-preproc")
+}
+
+fn now_s() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn mtime_s(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Charge l'index et le synchronise avec le contenu réel du dossier
+fn load_index(dir: &Path) -> CacheIndex {
+    let mut index = fs::read_to_string(dir.join(INDEX_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str::<CacheIndex>(&content).ok())
+        .unwrap_or_else(|| CacheIndex { max_bytes: DEFAULT_MAX_BYTES, entries: HashMap::new(), pinned: HashMap::new() });
+
+    // Les fichiers supprimés à la main disparaissent de l'index
+    index.entries.retain(|file, _| dir.join(file).is_file());
+
+    // Les fichiers écrits avant l'index (ou sans lui) sont repris, datés de leur dernière modification
+    if let Ok(read_dir) = fs::read_dir(dir) {
+        for entry in read_dir.flatten() {
+            let file = entry.file_name().to_string_lossy().to_string();
+            if !file.ends_with(".mp4") || index.entries.contains_key(&file) {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            index.entries.insert(file.clone(), CacheEntry {
+                file,
+                source: String::new(),
+                size_bytes: meta.len(),
+                last_used: mtime_s(&meta),
+                projects: Vec::new(),
+            });
+        }
+    }
+
+    index
+}
+
+fn save_index(dir: &Path, index: &CacheIndex) {
+    match serde_json::to_string_pretty(index) {
+        Ok(content) => {
+            let tmp_path = dir.join(format!("{}.tmp", INDEX_FILE));
+            if let Err(e) = fs::write(&tmp_path, content).and_then(|_| fs::rename(&tmp_path, dir.join(INDEX_FILE))) {
+                println!("[preproc_cache] Impossible d'enregistrer l'index: {}", e);
+            }
+        }
+        Err(e) => println!("[preproc_cache] Erreur sérialisation index: {}", e),
+    }
+}
+
+fn with_index<T>(f: impl FnOnce(&Path, &mut CacheIndex) -> T) -> Option<T> {
+    let dir = cache_dir();
+    fs::create_dir_all(&dir).ok()?;
+    let mut guard = CACHE_INDEX.lock().ok()?;
+    let index = guard.get_or_insert_with(|| load_index(&dir));
+    Some(f(&dir, index))
+}
+
+/// Supprime des entrées de l'index et leurs fichiers. Les fichiers encore ouverts (Windows) sont gardés.
+fn remove_entries(dir: &Path, index: &mut CacheIndex, files: &[String]) -> PurgeResult {
+    let mut result = PurgeResult::default();
+    for file in files {
+        let Some(entry) = index.entries.get(file) else {
+            continue;
+        };
+        let path = dir.join(file);
+        if path.exists() && fs::remove_file(&path).is_err() {
+            println!("[preproc_cache] Fichier en cours d'utilisation, conservé: {}", file);
+            continue;
+        }
+        result.removed_files += 1;
+        result.freed_bytes += entry.size_bytes;
+        index.entries.remove(file);
+    }
+    result
+}
+
+fn total_bytes(index: &CacheIndex) -> u64 {
+    index.entries.values().map(|e| e.size_bytes).sum()
+}
+
+fn is_pinned(index: &CacheIndex, file: &str) -> bool {
+    index.pinned.values().any(|files| files.contains(file))
+}
+
+/// Garde les fichiers non utilisés par un export en cours, en signalant ceux qui sont conservés
+fn unpinned(index: &CacheIndex, files: Vec<String>) -> Vec<String> {
+    let (pinned, free): (Vec<String>, Vec<String>) = files.into_iter().partition(|f| is_pinned(index, f));
+    if !pinned.is_empty() {
+        println!("[preproc_cache] {} fichier(s) utilisé(s) par un export en cours, conservé(s)", pinned.len());
+    }
+    free
+}
+
+/// Fichiers à évincer, du moins récemment utilisé au plus récent, pour repasser sous la taille maximale
+fn eviction_order(index: &CacheIndex) -> Vec<String> {
+    let total = total_bytes(index);
+    if total <= index.max_bytes {
+        return Vec::new();
+    }
+
+    let mut by_age: Vec<&CacheEntry> = index.entries.values().filter(|e| !is_pinned(index, &e.file)).collect();
+    by_age.sort_by(|a, b| a.last_used.cmp(&b.last_used).then_with(|| a.file.cmp(&b.file)));
+
+    let mut excess = total - index.max_bytes;
+    let mut evicted = Vec::new();
+    for entry in by_age {
+        if excess == 0 {
+            break;
+        }
+        excess = excess.saturating_sub(entry.size_bytes);
+        evicted.push(entry.file.clone());
+    }
+    evicted
+}
+
+/// Libère les fichiers utilisés par un export terminé (succès, échec ou annulation)
+pub(crate) fn release(export_id: &str) {
+    with_index(|_, index| {
+        index.pinned.remove(export_id);
+    });
+}
+
+/// Chemin du fichier prétraité pour `source`.
+///
+/// La clé inclut la date de modification et la taille de la source : une source modifiée
+/// donne un nouveau fichier, l'ancien finit par être évincé.
+pub(crate) fn entry_path(prefix: &str, source: &str, params: &str, suffix: &str) -> PathBuf {
+    let (mtime, size) = fs::metadata(source).map(|m| (mtime_s(&m), m.len())).unwrap_or((0, 0));
+    let hash_input = format!("{}-{}-{}-{}", source, mtime, size, params);
+    let stem_hash = format!("{:x}", md5::compute(hash_input.as_bytes()));
+    let stem_hash = &stem_hash[..10.min(stem_hash.len())];
+    cache_dir().join(format!("{}-{}-{}.mp4", prefix, stem_hash, suffix))
+}
+
+/// Vrai si le fichier est déjà en cache ; sa date d'utilisation est alors mise à jour
+/// et il reste réservé à l'export jusqu'à `release`
+pub(crate) fn touch(path: &Path, export_id: &str, project_id: Option<&str>) -> bool {
+    let Some(file) = path.file_name().map(|f| f.to_string_lossy().to_string()) else {
+        return false;
+    };
+    with_index(|dir, index| {
+        let Some(entry) = index.entries.get_mut(&file).filter(|_| path.is_file()) else {
+            return false;
+        };
+        entry.last_used = now_s();
+        if let Some(project_id) = project_id {
+            if !entry.projects.iter().any(|p| p == project_id) {
+                entry.projects.push(project_id.to_string());
+            }
+        }
+        index.pinned.entry(export_id.to_string()).or_default().insert(file);
+        save_index(dir, index);
+        true
+    })
+    .unwrap_or(false)
+}
+
+/// Enregistre un fichier qui vient d'être prétraité, réservé à l'export jusqu'à `release`
+pub(crate) fn record(path: &Path, source: &str, export_id: &str, project_id: Option<&str>) {
+    let Some(file) = path.file_name().map(|f| f.to_string_lossy().to_string()) else {
+        return;
+    };
+    let size_bytes = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    with_index(|dir, index| {
+        index.pinned.entry(export_id.to_string()).or_default().insert(file.clone());
+        index.entries.insert(file.clone(), CacheEntry {
+            file,
+            source: source.to_string(),
+            size_bytes,
+            last_used: now_s(),
+            projects: project_id.map(|p| vec![p.to_string()]).unwrap_or_default(),
+        });
+        save_index(dir, index);
+    });
+}
+
+/// Évince les fichiers les moins récemment utilisés jusqu'à repasser sous la taille maximale.
+///
+/// Les fichiers réservés par un export en cours ne sont jamais évincés.
+pub(crate) fn enforce_limit() {
+    with_index(|dir, index| {
+        let evicted = eviction_order(index);
+        if evicted.is_empty() {
+            return;
+        }
+
+        let result = remove_entries(dir, index, &evicted);
+        println!(
+            "[preproc_cache] {} fichier(s) évincé(s), {:.1} Mo libérés",
+            result.removed_files,
+            result.freed_bytes as f64 / (1024.0 * 1024.0)
+        );
+        save_index(dir, index);
+    });
+}
+
+#[tauri::command]
+pub fn get_preproc_cache_stats() -> Result<PreprocCacheStats, ExportError> {
+    with_index(|dir, index| {
+        let mut entries: Vec<CacheEntry> = index.entries.values().cloned().collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.last_used));
+        PreprocCacheStats {
+            dir: dir.to_string_lossy().to_string(),
+            total_bytes: total_bytes(index),
+            max_bytes: index.max_bytes,
+            file_count: entries.len(),
+            entries,
+        }
+    })
+    .ok_or_else(|| ExportError::io("Cache de prétraitement inaccessible"))
+}
+
+#[tauri::command]
+pub fn purge_preproc_cache() -> Result<PurgeResult, ExportError> {
+    with_index(|dir, index| {
+        let files = unpinned(index, index.entries.keys().cloned().collect());
+        let result = remove_entries(dir, index, &files);
+        save_index(dir, index);
+        println!("[preproc_cache] Cache vidé: {} fichier(s)", result.removed_files);
+        result
+    })
+    .ok_or_else(|| ExportError::io("Cache de prétraitement inaccessible"))
+}
+
+/// Supprime les fichiers utilisés par un projet (y compris ceux partagés avec d'autres projets),
+/// sauf ceux d'un export en cours
+#[tauri::command]
+pub fn purge_preproc_cache_for_project(project_id: String) -> Result<PurgeResult, ExportError> {
+    with_index(|dir, index| {
+        let files: Vec<String> = index
+            .entries
+            .values()
+            .filter(|e| e.projects.iter().any(|p| p == &project_id))
+            .map(|e| e.file.clone())
+            .collect();
+        let files = unpinned(index, files);
+        let result = remove_entries(dir, index, &files);
+        save_index(dir, index);
+        println!("[preproc_cache] Projet {}: {} fichier(s) supprimé(s)", project_id, result.removed_files);
+        result
+    })
+    .ok_or_else(|| ExportError::io("Cache de prétraitement inaccessible"))
+}
+
+/// Change la taille maximale du cache (en Mo) et évince immédiatement si besoin
+#[tauri::command]
+pub fn set_preproc_cache_limit(max_mb: u64) -> Result<(), ExportError> {
+    with_index(|dir, index| {
+        index.max_bytes = max_mb.saturating_mul(1024 * 1024);
+        save_index(dir, index);
+    })
+    .ok_or_else(|| ExportError::io("Cache de prétraitement inaccessible"))?;
+    enforce_limit();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, size_bytes: u64, last_used: u64) -> CacheEntry {
+        CacheEntry { file: file.to_string(), source: String::new(), size_bytes, last_used, projects: Vec::new() }
+    }
+
+    fn index(max_bytes: u64, entries: Vec<CacheEntry>) -> CacheIndex {
+        CacheIndex {
+            max_bytes,
+            entries: entries.into_iter().map(|e| (e.file.clone(), e)).collect(),
+            pinned: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn least_recently_used_files_are_evicted_first() {
+        let index = index(250, vec![entry("c.mp4", 100, 30), entry("a.mp4", 100, 10), entry("b.mp4", 100, 20), entry("d.mp4", 100, 40)]);
+        assert_eq!(eviction_order(&index), vec!["a.mp4", "b.mp4"]);
+    }
+
+    #[test]
+    fn nothing_is_evicted_under_the_limit() {
+        let index = index(300, vec![entry("a.mp4", 100, 10), entry("b.mp4", 200, 20)]);
+        assert!(eviction_order(&index).is_empty());
+    }
+
+    #[test]
+    fn files_pinned_by_an_active_export_are_kept() {
+        let mut index = index(250, vec![entry("a.mp4", 100, 10), entry("b.mp4", 100, 20), entry("c.mp4", 100, 30)]);
+        index.pinned.entry("export-1".to_string()).or_default().insert("a.mp4".to_string());
+        assert_eq!(eviction_order(&index), vec!["b.mp4"]);
+        assert_eq!(unpinned(&index, vec!["a.mp4".to_string(), "c.mp4".to_string()]), vec!["c.mp4"]);
+
+        index.pinned.remove("export-1");
+        assert_eq!(eviction_order(&index), vec!["a.mp4"]);
+    }
+
+    #[test]
+    fn entry_path_changes_with_the_source_size_and_mtime() {
+        let dir = std::env::temp_dir().join(format!("qurancaption-preproc-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.mp4");
+        let source_str = source.to_string_lossy().to_string();
+        fs::write(&source, b"0123456789").unwrap();
+        let file = fs::File::options().write(true).open(&source).unwrap();
+        file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(1_000_000)).unwrap();
+
+        let first = entry_path("bg", &source_str, "1920x1080", "0");
+        assert_eq!(first, entry_path("bg", &source_str, "1920x1080", "0"));
+        assert_ne!(first, entry_path("bg", &source_str, "1280x720", "0"));
+
+        // Même taille, date de modification différente
+        file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(2_000_000)).unwrap();
+        let touched = entry_path("bg", &source_str, "1920x1080", "0");
+        assert_ne!(first, touched);
+
+        // Même date de modification, taille différente
+        file.set_len(20).unwrap();
+        file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(2_000_000)).unwrap();
+        assert_ne!(touched, entry_path("bg", &source_str, "1920x1080", "0"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
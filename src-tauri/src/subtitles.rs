@@ -0,0 +1,636 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::exporter::ExportError;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+    Ttml,
+}
+
+/// Un clip de sous-titre : texte arabe + traductions (clé = target de la traduction)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SubtitleCue {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub arabic: Option<String>,
+    #[serde(default)]
+    pub translations: HashMap<String, String>,
+}
+
+impl SubtitleCue {
+    fn text_for(&self, target: &str) -> Option<&str> {
+        let text = if target == "arabic" {
+            self.arabic.as_deref()
+        } else {
+            self.translations.get(target).map(|s| s.as_str())
+        };
+        text.map(|t| t.trim()).filter(|t| !t.is_empty())
+    }
+}
+
+/// Sous-ensemble du `VideoStyle` d'une target utile aux sous-titres.
+///
+/// Les noms de champs reprennent les ids des styles du projet (`text-color`, `font-size`, ...).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SubtitleStyle {
+    pub text_color: String,
+    pub font_size: f64,
+    pub font_family: String,
+    pub font_weight: String,
+    pub vertical_position: f64,
+    pub horizontal_position: f64,
+    pub outline_enable: bool,
+    pub text_outline: f64,
+    pub text_outline_color: String,
+    pub background_enable: bool,
+    pub background_color: String,
+}
+
+impl Default for SubtitleStyle {
+    fn default() -> Self {
+        SubtitleStyle {
+            text_color: "#ffffff".to_string(),
+            font_size: 60.0,
+            font_family: "Candara".to_string(),
+            font_weight: "normal".to_string(),
+            vertical_position: 0.0,
+            horizontal_position: 0.0,
+            outline_enable: false,
+            text_outline: 0.25,
+            text_outline_color: "#000000".to_string(),
+            background_enable: false,
+            background_color: "rgba(0,0,0,0.8)".to_string(),
+        }
+    }
+}
+
+/// Une piste de sous-titres : la target (`arabic` ou une traduction), sa langue et son style
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SubtitleTrack {
+    pub target: String,
+    pub language: Option<String>,
+    #[serde(default)]
+    pub style: SubtitleStyle,
+}
+
+impl SubtitleTrack {
+    pub fn language_code(&self) -> &str {
+        match self.language.as_deref() {
+            Some(lang) if !lang.is_empty() => lang,
+            _ if self.target == "arabic" => "ar",
+            _ => "und",
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SubtitleExportOptions {
+    pub format: SubtitleFormat,
+    pub output_path: String,
+    pub tracks: Vec<SubtitleTrack>,
+    #[serde(default = "default_video_width")]
+    pub video_width: u32,
+    #[serde(default = "default_video_height")]
+    pub video_height: u32,
+}
+
+fn default_video_width() -> u32 {
+    1920
+}
+
+fn default_video_height() -> u32 {
+    1080
+}
+
+/// Parse une couleur CSS (`#rgb`, `#rrggbb`, `#rrggbbaa`, `rgb()`, `rgba()`) en (r, g, b, alpha 0..1)
+fn parse_css_color(color: &str) -> Option<(u8, u8, u8, f64)> {
+    let c = color.trim().to_lowercase();
+
+    if let Some(hex) = c.strip_prefix('#') {
+        let expanded: String = if hex.len() == 3 || hex.len() == 4 {
+            hex.chars().flat_map(|ch| [ch, ch]).collect()
+        } else {
+            hex.to_string()
+        };
+        let byte = |i: usize| u8::from_str_radix(expanded.get(i..i + 2)?, 16).ok();
+        return match expanded.len() {
+            6 => Some((byte(0)?, byte(2)?, byte(4)?, 1.0)),
+            8 => Some((byte(0)?, byte(2)?, byte(4)?, byte(6)? as f64 / 255.0)),
+            _ => None,
+        };
+    }
+
+    let inner = c
+        .strip_prefix("rgba(")
+        .or_else(|| c.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+    let parts: Vec<&str> = inner.split(',').map(|p| p.trim()).collect();
+    if parts.len() < 3 {
+        return None;
+    }
+    let channel = |p: &str| p.parse::<f64>().ok().map(|v| v.clamp(0.0, 255.0).round() as u8);
+    let alpha = parts.get(3).and_then(|a| a.parse::<f64>().ok()).unwrap_or(1.0).clamp(0.0, 1.0);
+    Some((channel(parts[0])?, channel(parts[1])?, channel(parts[2])?, alpha))
+}
+
+// Couleur ASS : &HAABBGGRR avec alpha inversé (00 = opaque)
+fn ass_color(color: &str, fallback: &str) -> String {
+    let (r, g, b, a) = parse_css_color(color)
+        .or_else(|| parse_css_color(fallback))
+        .unwrap_or((255, 255, 255, 1.0));
+    let alpha = 255 - (a * 255.0).round() as u8;
+    format!("&H{:02X}{:02X}{:02X}{:02X}", alpha, b, g, r)
+}
+
+// Couleur TTML : #rrggbbaa
+fn ttml_color(color: &str, fallback: &str) -> String {
+    let (r, g, b, a) = parse_css_color(color)
+        .or_else(|| parse_css_color(fallback))
+        .unwrap_or((255, 255, 255, 1.0));
+    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, (a * 255.0).round() as u8)
+}
+
+fn is_bold(font_weight: &str) -> bool {
+    match font_weight.trim() {
+        "bold" | "bolder" => true,
+        w => w.parse::<u32>().map(|n| n >= 600).unwrap_or(false),
+    }
+}
+
+fn split_ms(ms: i64) -> (i64, i64, i64, i64) {
+    let ms = ms.max(0);
+    (ms / 3_600_000, (ms % 3_600_000) / 60_000, (ms % 60_000) / 1000, ms % 1000)
+}
+
+fn format_srt_time(ms: i64) -> String {
+    let (h, m, s, millis) = split_ms(ms);
+    format!("{:02}:{:02}:{:02},{:03}", h, m, s, millis)
+}
+
+fn format_vtt_time(ms: i64) -> String {
+    let (h, m, s, millis) = split_ms(ms);
+    format!("{:02}:{:02}:{:02}.{:03}", h, m, s, millis)
+}
+
+fn format_ass_time(ms: i64) -> String {
+    let (h, m, s, millis) = split_ms(ms);
+    format!("{}:{:02}:{:02}.{:02}", h, m, s, millis / 10)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Nom de style ASS/TTML sans caractères spéciaux
+fn style_id(target: &str) -> String {
+    target
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+// Textes d'un clip pour toutes les pistes, une ligne par piste (SRT/VTT)
+fn joined_text(cue: &SubtitleCue, tracks: &[SubtitleTrack]) -> Option<String> {
+    let lines: Vec<&str> = tracks.iter().filter_map(|t| cue.text_for(&t.target)).collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+fn generate_srt(cues: &[SubtitleCue], tracks: &[SubtitleTrack]) -> String {
+    let mut out = String::new();
+    let mut index = 1;
+    for cue in cues {
+        let Some(text) = joined_text(cue, tracks) else {
+            continue;
+        };
+        let _ = write!(out, "{}\n{} --> {}\n{}\n\n", index, format_srt_time(cue.start_ms), format_srt_time(cue.end_ms), text);
+        index += 1;
+    }
+    out
+}
+
+fn generate_vtt(cues: &[SubtitleCue], tracks: &[SubtitleTrack]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    let mut index = 1;
+    for cue in cues {
+        let Some(text) = joined_text(cue, tracks) else {
+            continue;
+        };
+        let text = text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        let _ = write!(out, "{}\n{} --> {}\n{}\n\n", index, format_vtt_time(cue.start_ms), format_vtt_time(cue.end_ms), text);
+        index += 1;
+    }
+    out
+}
+
+fn generate_ass(cues: &[SubtitleCue], tracks: &[SubtitleTrack], width: u32, height: u32) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "[Script Info]\nScriptType: v4.00+\nPlayResX: {}\nPlayResY: {}\nWrapStyle: 0\nScaledBorderAndShadow: yes\n\n",
+        width, height
+    );
+
+    out.push_str("[V4+ Styles]\n");
+    out.push_str("Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n");
+    for track in tracks {
+        let style = &track.style;
+        let primary = ass_color(&style.text_color, "#ffffff");
+
+        // BorderStyle 3 = boîte opaque (fond activé), 1 = contour classique
+        let (border_style, outline_colour, back_colour, outline) = if style.background_enable {
+            let back = ass_color(&style.background_color, "rgba(0,0,0,0.8)");
+            (3, back.clone(), back, 4.0)
+        } else if style.outline_enable {
+            (1, ass_color(&style.text_outline_color, "#000000"), "&H80000000".to_string(), style.text_outline.max(1.0))
+        } else {
+            (1, "&H00000000".to_string(), "&H80000000".to_string(), 0.0)
+        };
+
+        let _ = writeln!(
+            out,
+            "Style: {},{},{},{},{},{},{},{},0,0,0,100,100,0,0,{},{:.2},0,5,10,10,10,1",
+            style_id(&track.target),
+            style.font_family.replace(',', " "),
+            style.font_size.round(),
+            primary,
+            primary,
+            outline_colour,
+            back_colour,
+            if is_bold(&style.font_weight) { -1 } else { 0 },
+            border_style,
+            outline
+        );
+    }
+
+    out.push_str("\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n");
+    for cue in cues {
+        for (layer, track) in tracks.iter().enumerate() {
+            let Some(text) = cue.text_for(&track.target) else {
+                continue;
+            };
+            // Les positions du VideoStyle sont des décalages (px) depuis le centre de la vidéo
+            let x = width as f64 / 2.0 + track.style.horizontal_position;
+            let y = height as f64 / 2.0 + track.style.vertical_position;
+            let text = text.replace('{', "(").replace('}', ")").replace('\n', "\\N");
+            let _ = writeln!(
+                out,
+                "Dialogue: {},{},{},{},,0,0,0,,{{\\pos({:.0},{:.0})}}{}",
+                layer,
+                format_ass_time(cue.start_ms),
+                format_ass_time(cue.end_ms),
+                style_id(&track.target),
+                x,
+                y,
+                text
+            );
+        }
+    }
+    out
+}
+
+fn generate_ttml(cues: &[SubtitleCue], tracks: &[SubtitleTrack], width: u32, height: u32) -> String {
+    let main_lang = tracks.first().map(|t| t.language_code()).unwrap_or("und");
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<tt xmlns=\"http://www.w3.org/ns/ttml\" xmlns:tts=\"http://www.w3.org/ns/ttml#styling\" xmlns:ttp=\"http://www.w3.org/ns/ttml#parameter\" ttp:timeBase=\"media\" tts:extent=\"{}px {}px\" xml:lang=\"{}\">",
+        width,
+        height,
+        escape_xml(main_lang)
+    );
+
+    out.push_str("  <head>\n    <styling>\n");
+    for track in tracks {
+        let style = &track.style;
+        let mut attrs = format!(
+            "tts:color=\"{}\" tts:fontFamily=\"{}\" tts:fontSize=\"{:.0}px\" tts:fontWeight=\"{}\" tts:textAlign=\"center\"",
+            ttml_color(&style.text_color, "#ffffff"),
+            escape_xml(&style.font_family),
+            style.font_size,
+            if is_bold(&style.font_weight) { "bold" } else { "normal" }
+        );
+        if style.background_enable {
+            let _ = write!(attrs, " tts:backgroundColor=\"{}\"", ttml_color(&style.background_color, "rgba(0,0,0,0.8)"));
+        }
+        if style.outline_enable {
+            let _ = write!(attrs, " tts:textOutline=\"{} {:.0}px\"", ttml_color(&style.text_outline_color, "#000000"), style.text_outline.max(1.0));
+        }
+        let _ = writeln!(out, "      <style xml:id=\"s_{}\" {}/>", style_id(&track.target), attrs);
+    }
+    out.push_str("    </styling>\n    <layout>\n");
+    for track in tracks {
+        // Région de 80% x 20% centrée sur la position du style
+        let center_x = (width as f64 / 2.0 + track.style.horizontal_position) / width as f64 * 100.0;
+        let center_y = (height as f64 / 2.0 + track.style.vertical_position) / height as f64 * 100.0;
+        let _ = writeln!(
+            out,
+            "      <region xml:id=\"r_{}\" tts:origin=\"{:.2}% {:.2}%\" tts:extent=\"80% 20%\" tts:displayAlign=\"center\"/>",
+            style_id(&track.target),
+            (center_x - 40.0).clamp(0.0, 20.0),
+            (center_y - 10.0).clamp(0.0, 80.0)
+        );
+    }
+    out.push_str("    </layout>\n  </head>\n  <body>\n");
+
+    for track in tracks {
+        let id = style_id(&track.target);
+        let _ = writeln!(
+            out,
+            "    <div xml:lang=\"{}\" style=\"s_{}\" region=\"r_{}\">",
+            escape_xml(track.language_code()),
+            id,
+            id
+        );
+        for cue in cues {
+            let Some(text) = cue.text_for(&track.target) else {
+                continue;
+            };
+            let text = text.lines().map(escape_xml).collect::<Vec<_>>().join("<br/>");
+            let _ = writeln!(
+                out,
+                "      <p begin=\"{}\" end=\"{}\">{}</p>",
+                format_vtt_time(cue.start_ms),
+                format_vtt_time(cue.end_ms),
+                text
+            );
+        }
+        out.push_str("    </div>\n");
+    }
+    out.push_str("  </body>\n</tt>\n");
+    out
+}
+
+/// Génère le contenu d'un fichier de sous-titres dans le format demandé
+pub fn render_subtitles(cues: &[SubtitleCue], options: &SubtitleExportOptions) -> String {
+    // Clips triés et sans durée nulle
+    let mut cues: Vec<SubtitleCue> = cues.iter().filter(|c| c.end_ms > c.start_ms).cloned().collect();
+    cues.sort_by_key(|c| c.start_ms);
+
+    let (w, h) = (options.video_width.max(1), options.video_height.max(1));
+    match options.format {
+        SubtitleFormat::Srt => generate_srt(&cues, &options.tracks),
+        SubtitleFormat::Vtt => generate_vtt(&cues, &options.tracks),
+        SubtitleFormat::Ass => generate_ass(&cues, &options.tracks, w, h),
+        SubtitleFormat::Ttml => generate_ttml(&cues, &options.tracks, w, h),
+    }
+}
+
+/// Pistes de sous-titres "soft" à multiplexer dans le conteneur de sortie (une piste par target)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SoftSubtitleOptions {
+    pub cues: Vec<SubtitleCue>,
+    pub tracks: Vec<SubtitleTrack>,
+    // En MKV, utiliser ASS (stylé) plutôt que SRT
+    #[serde(default)]
+    pub prefer_ass: bool,
+}
+
+/// Fichiers de sous-titres temporaires prêts à être ajoutés comme entrées ffmpeg
+pub(crate) struct SoftSubtitleStreams {
+    files: Vec<(PathBuf, String, String)>,
+    codec: &'static str,
+}
+
+// ffmpeg attend des codes ISO 639-2 (3 lettres) pour le tag de langue en MP4
+fn iso639_2(code: &str) -> String {
+    let code = code.to_lowercase();
+    let mapped = match code.split(['-', '_']).next().unwrap_or("") {
+        "ar" => "ara",
+        "en" => "eng",
+        "fr" => "fra",
+        "de" => "deu",
+        "es" => "spa",
+        "it" => "ita",
+        "pt" => "por",
+        "ru" => "rus",
+        "tr" => "tur",
+        "ur" => "urd",
+        "fa" => "fas",
+        "id" => "ind",
+        "ms" => "msa",
+        "bn" => "ben",
+        "hi" => "hin",
+        "zh" => "zho",
+        "nl" => "nld",
+        "sq" => "sqi",
+        "bs" => "bos",
+        "sw" => "swa",
+        "so" => "som",
+        "ha" => "hau",
+        "uz" => "uzb",
+        "az" => "aze",
+        "ta" => "tam",
+        "ja" => "jpn",
+        "ko" => "kor",
+        _ => return code,
+    };
+    mapped.to_string()
+}
+
+impl SoftSubtitleStreams {
+    pub(crate) fn input_args(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .flat_map(|(path, _, _)| ["-i".to_string(), path.to_string_lossy().to_string()])
+            .collect()
+    }
+
+    /// Arguments de sortie (mapping, codec, métadonnées) pour des entrées commençant à `first_input_idx`
+    pub(crate) fn output_args(&self, first_input_idx: usize) -> Vec<String> {
+        let mut args = Vec::new();
+        for i in 0..self.files.len() {
+            args.extend_from_slice(&["-map".to_string(), format!("{}:s", first_input_idx + i)]);
+        }
+        args.extend_from_slice(&["-c:s".to_string(), self.codec.to_string()]);
+        for (i, (_, language, title)) in self.files.iter().enumerate() {
+            args.extend_from_slice(&[
+                format!("-metadata:s:s:{}", i),
+                format!("language={}", language),
+                format!("-metadata:s:s:{}", i),
+                format!("title={}", title),
+            ]);
+        }
+        args
+    }
+
+    pub(crate) fn cleanup(&self) {
+        for (path, _, _) in &self.files {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Écrit un fichier de sous-titres par piste pour la fenêtre `[start_ms, start_ms + duration_ms]`
+/// de l'export (timings décalés pour commencer à 0).
+///
+/// Les fichiers sont nommés d'après l'export et la sortie, `dir` pouvant être partagé entre exports.
+///
+/// Renvoie `None` si le conteneur ne supporte pas les sous-titres ou si aucune piste n'a de texte.
+pub(crate) fn prepare_soft_subtitles(
+    options: &SoftSubtitleOptions,
+    export_id: &str,
+    out_path: &str,
+    start_ms: i64,
+    duration_ms: Option<i64>,
+    video_size: (u32, u32),
+    dir: &Path,
+) -> Result<Option<SoftSubtitleStreams>, ExportError> {
+    let ext = Path::new(out_path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    let (format, codec) = match ext.as_str() {
+        "mp4" | "mov" | "m4v" => (SubtitleFormat::Srt, "mov_text"),
+        "mkv" if options.prefer_ass => (SubtitleFormat::Ass, "ass"),
+        "mkv" => (SubtitleFormat::Srt, "srt"),
+        "webm" => (SubtitleFormat::Vtt, "webvtt"),
+        _ => {
+            println!("[subtitles] Conteneur .{} sans support des sous-titres soft, ignoré", ext);
+            return Ok(None);
+        }
+    };
+
+    let out_stem = Path::new(out_path).file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let end_ms = duration_ms.map(|d| start_ms + d).unwrap_or(i64::MAX);
+    let cues: Vec<SubtitleCue> = options
+        .cues
+        .iter()
+        .filter(|c| c.end_ms > start_ms && c.start_ms < end_ms)
+        .map(|c| SubtitleCue {
+            start_ms: c.start_ms.max(start_ms) - start_ms,
+            end_ms: c.end_ms.min(end_ms) - start_ms,
+            ..c.clone()
+        })
+        .collect();
+
+    fs::create_dir_all(dir)?;
+    let mut files = Vec::new();
+    for track in &options.tracks {
+        if !cues.iter().any(|c| c.text_for(&track.target).is_some()) {
+            continue;
+        }
+
+        let path = dir.join(format!("soft-subs-{}-{}-{}.{}", export_id, out_stem, style_id(&track.target), match format {
+            SubtitleFormat::Ass => "ass",
+            SubtitleFormat::Vtt => "vtt",
+            _ => "srt",
+        }));
+        let export_options = SubtitleExportOptions {
+            format,
+            output_path: path.to_string_lossy().to_string(),
+            tracks: vec![track.clone()],
+            video_width: video_size.0,
+            video_height: video_size.1,
+        };
+        fs::write(&path, render_subtitles(&cues, &export_options))?;
+        files.push((path, iso639_2(track.language_code()), track.target.clone()));
+    }
+
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    println!("[subtitles] {} piste(s) soft ({}) ajoutée(s) à {}", files.len(), codec, out_path);
+    Ok(Some(SoftSubtitleStreams { files, codec }))
+}
+
+#[tauri::command]
+pub fn export_subtitles(cues: Vec<SubtitleCue>, options: SubtitleExportOptions) -> Result<String, ExportError> {
+    if options.tracks.is_empty() {
+        return Err(ExportError::invalid_input("Aucune piste de sous-titres sélectionnée"));
+    }
+
+    println!("[subtitles] Export {} clip(s) en {:?} -> {}", cues.len(), options.format, options.output_path);
+
+    let content = render_subtitles(&cues, &options);
+
+    if let Some(parent) = Path::new(&options.output_path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&options.output_path, content)?;
+
+    Ok(options.output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(target: &str) -> SubtitleTrack {
+        SubtitleTrack { target: target.to_string(), language: None, style: SubtitleStyle::default() }
+    }
+
+    fn cue(start_ms: i64, end_ms: i64, arabic: Option<&str>, translation: Option<&str>) -> SubtitleCue {
+        SubtitleCue {
+            start_ms,
+            end_ms,
+            arabic: arabic.map(str::to_string),
+            translations: translation.map(|t| HashMap::from([("en".to_string(), t.to_string())])).unwrap_or_default(),
+        }
+    }
+
+    #[test]
+    fn css_colors_are_parsed() {
+        assert_eq!(parse_css_color("#fff"), Some((255, 255, 255, 1.0)));
+        assert_eq!(parse_css_color("#FF000080"), Some((255, 0, 0, 128.0 / 255.0)));
+        assert_eq!(parse_css_color("rgba(0, 128, 255, 0.5)"), Some((0, 128, 255, 0.5)));
+        assert_eq!(parse_css_color("rgb(300, 0, 0)"), Some((255, 0, 0, 1.0)));
+        assert_eq!(parse_css_color("red"), None);
+    }
+
+    #[test]
+    fn colors_are_converted_for_ass_and_ttml() {
+        assert_eq!(ass_color("#112233", "#ffffff"), "&H00332211");
+        assert_eq!(ass_color("rgba(0,0,0,0.8)", "#ffffff"), "&H33000000");
+        assert_eq!(ass_color("invalid", "#000"), "&H00000000");
+        assert_eq!(ttml_color("#112233", "#ffffff"), "#112233ff");
+    }
+
+    #[test]
+    fn times_are_formatted_per_format() {
+        let ms = 3_723_456;
+        assert_eq!(format_srt_time(ms), "01:02:03,456");
+        assert_eq!(format_vtt_time(ms), "01:02:03.456");
+        assert_eq!(format_ass_time(ms), "1:02:03.45");
+        assert_eq!(format_srt_time(-10), "00:00:00,000");
+    }
+
+    #[test]
+    fn srt_joins_tracks_and_skips_empty_cues() {
+        let cues = vec![
+            cue(0, 1500, Some("بسم الله"), Some("In the name of God")),
+            cue(1500, 2000, Some("  "), None),
+            cue(2000, 3000, None, Some("Praise")),
+        ];
+        let srt = generate_srt(&cues, &[track("arabic"), track("en")]);
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:01,500\nبسم الله\nIn the name of God\n\n\
+             2\n00:00:02,000 --> 00:00:03,000\nPraise\n\n"
+        );
+    }
+
+    #[test]
+    fn vtt_escapes_markup() {
+        let vtt = generate_vtt(&[cue(0, 1000, None, Some("a < b & c"))], &[track("en")]);
+        assert_eq!(vtt, "WEBVTT\n\n1\n00:00:00.000 --> 00:00:01.000\na &lt; b &amp; c\n\n");
+    }
+}
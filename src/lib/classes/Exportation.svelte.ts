@@ -27,6 +27,9 @@ export default class Exportation extends SerializableBase {
 	percentageProgress: number;
 	currentTreatedTime: number;
 	errorLog: string;
+	fileSize: number;
+	// Mis à jour par l'événement de progression émis par Rust à chaque pause/reprise
+	isPaused: boolean;
 	fps: number;
 	date: string;
 
@@ -58,6 +61,8 @@ export default class Exportation extends SerializableBase {
 		this.percentageProgress = $state(percentageProgress);
 		this.currentTreatedTime = $state(currentTreatedTime);
 		this.errorLog = $state(errorLog);
+		this.fileSize = $state(0);
+		this.isPaused = $state(false);
 		this.date = $state(new Date().toISOString());
 	}
 
@@ -72,6 +77,21 @@ export default class Exportation extends SerializableBase {
 		);
 	}
 
+	/**
+	 * Vrai si l'encodage FFmpeg (côté Rust) est en cours : c'est lui qui peut être mis en pause
+	 */
+	canBePaused() {
+		return this.currentState === ExportState.CreatingVideo;
+	}
+
+	async pauseExport() {
+		await invoke('pause_export', { exportId: this.exportId.toString() });
+	}
+
+	async resumeExport() {
+		await invoke('resume_export', { exportId: this.exportId.toString() });
+	}
+
 	async cancelExport() {
 		if (
 			this.currentState === ExportState.Initializing ||
@@ -93,5 +113,6 @@ export default class Exportation extends SerializableBase {
 
 		// Set state to canceled
 		this.currentState = ExportState.Canceled;
+		this.isPaused = false;
 	}
 }
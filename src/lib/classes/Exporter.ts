@@ -9,6 +9,7 @@ import type { Project } from '.';
 import ExportService from '$lib/services/ExportService';
 import { BaseDirectory, join } from '@tauri-apps/api/path';
 import { remove } from '@tauri-apps/plugin-fs';
+import { invoke } from '@tauri-apps/api/core';
 
 export default class Exporter {
 	/**
@@ -188,6 +189,9 @@ export default class Exporter {
 		// Génère un ID d'export unique.
 		const exportId = Utilities.randomId().toString();
 
+		// ID du projet exporté, pour rattacher le cache de prétraitement au bon projet
+		const projectId = globalState.currentProject!.detail.id.toString();
+
 		// Fait une copie du projet à l'état actuelle
 		const project = globalState.currentProject!.clone();
 		project.detail.id = Number(exportId); // L'ID du projet est l'ID d'export
@@ -211,12 +215,17 @@ export default class Exporter {
 			visible: false,
 			alwaysOnTop: false,
 			title: 'QC - ' + exportId,
-			url: '/exporter?' + new URLSearchParams({ id: exportId }) // Met en paramètre l'ID de l'export pour que l'exportateur puisse le récupérer
+			url: '/exporter?' + new URLSearchParams({ id: exportId, projectId }) // Met en paramètre l'ID de l'export pour que l'exportateur puisse le récupérer
 		});
 
 		// listen  to close
 		w.listen('tauri://close-requested', async (e) => {
 			try {
+				// Un export en chunks garde son dossier (images et vidéos des chunks) pour pouvoir
+				// être repris : il est supprimé par le backend à la fin du job ou à son abandon
+				const jobs = await invoke<{ export_id: string }[]>('list_export_jobs');
+				if (jobs.some((job) => job.export_id === exportId)) return;
+
 				// Supprime le dossier temporaire des images
 				await remove(await join(ExportService.exportFolder, exportId), {
 					baseDir: BaseDirectory.AppData,
@@ -234,6 +234,8 @@ export class ExportState extends SerializableBase {
 	videoEndTime: number = $state(0);
 	fps: number = $state(30);
 	chunkSize: number = $state(50);
+	// Nombre de chunks encodés en parallèle (0 = automatique)
+	exportWorkers: number = $state(0);
 }
 
 SerializableBase.registerChildClass(ProjectEditorState, 'timeline', TimelineState);
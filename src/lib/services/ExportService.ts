@@ -130,6 +130,7 @@ export default class ExportService {
 			if (exp.isOnGoing()) {
 				exp.currentState = ExportState.Canceled;
 			}
+			exp.isPaused = false;
 		});
 	}
 
@@ -167,6 +168,16 @@ function exportProgress(event: any): void {
 			return;
 		}
 
+		if (data.paused !== undefined) {
+			exportation.isPaused = data.paused;
+		}
+
+		// Simple changement de pause : la progression ne bouge pas
+		if (data.progress === undefined) {
+			ExportService.saveExports();
+			return;
+		}
+
 		exportation.percentageProgress = data.progress;
 		exportation.currentState = data.currentState;
 		exportation.currentTreatedTime = data.currentTime;
@@ -174,6 +185,14 @@ function exportProgress(event: any): void {
 		if (data.errorLog) {
 			exportation.errorLog = data.errorLog;
 		}
+
+		if (data.fileSize) {
+			exportation.fileSize = data.fileSize;
+		}
+
+		if (!exportation.isOnGoing()) {
+			exportation.isPaused = false;
+		}
 	}
 
 	ExportService.saveExports();
@@ -185,4 +204,6 @@ export interface ExportProgress {
 	currentState: ExportState;
 	currentTime: number;
 	errorLog?: string;
+	fileSize?: number;
+	paused?: boolean;
 }